@@ -1,11 +1,198 @@
+/// 構文解析によって得られる式を表します
+///
+/// 実行時エラーなどで位置を示せるように、各ノードは式の先頭のトークンが現れた行番号 `line` を保持します。
+///
+/// `PartialEq` は括弧 `Grouping` も区別して比較するため、`(1)` と `1` は等しくなりません。
+/// 括弧を無視して比較したい場合は `unwrap_grouping` で外側の括弧を取り除いてから比較してください。
 #[derive(PartialEq, Debug)]
 pub enum Expr {
-    Literal(Literal),
-    Unary(UnaryOp, Box<Expr>),
-    Binary(Box<Expr>, BinaryOp, Box<Expr>),
-    Grouping(Box<Expr>),
+    Literal {
+        value: Literal,
+        line: usize,
+    },
+    Unary {
+        op: UnaryOp,
+        right: Box<Expr>,
+        line: usize,
+    },
+    Binary {
+        left: Box<Expr>,
+        op: BinaryOp,
+        /// 演算子のトークンが現れた行番号
+        ///
+        /// 複数行にまたがる式では左辺の行番号 `line` と異なる場合がある
+        op_line: usize,
+        right: Box<Expr>,
+        line: usize,
+    },
+    Grouping {
+        expr: Box<Expr>,
+        line: usize,
+    },
+    /// `[1, 2, 3]` のような配列リテラル
+    Array {
+        elements: Vec<Expr>,
+        line: usize,
+    },
+    /// `{ "key": value }` のような連想配列リテラル
+    Map {
+        entries: Vec<(Expr, Expr)>,
+        line: usize,
+    },
+    /// `arr[i]` のような添字によるアクセス
+    Index {
+        object: Box<Expr>,
+        index: Box<Expr>,
+        line: usize,
+    },
+    /// `arr[i] = v` のような添字を指定した代入
+    IndexSet {
+        object: Box<Expr>,
+        index: Box<Expr>,
+        value: Box<Expr>,
+        line: usize,
+    },
 }
 
+impl Expr {
+    /// 式の先頭のトークンが現れた行番号を返します
+    pub fn line(&self) -> usize {
+        match self {
+            Expr::Literal { line, .. }
+            | Expr::Unary { line, .. }
+            | Expr::Binary { line, .. }
+            | Expr::Grouping { line, .. }
+            | Expr::Array { line, .. }
+            | Expr::Map { line, .. }
+            | Expr::Index { line, .. }
+            | Expr::IndexSet { line, .. } => *line,
+        }
+    }
+
+    /// 外側を囲んでいる冗長な括弧 `Grouping` をすべて取り除いた式を返します
+    ///
+    /// 取り除くのは最も外側の括弧のみであり、部分式の内側にある括弧はそのまま残ります。
+    pub fn unwrap_grouping(&self) -> &Expr {
+        let mut expr = self;
+        while let Expr::Grouping { expr: inner, .. } = expr {
+            expr = inner;
+        }
+        expr
+    }
+
+    /// 式の入れ子の深さを返します。リテラルのように部分式を持たない式の深さは `1` です。
+    ///
+    /// 深く入れ子になった式でもスタックが溢れないように、明示的なスタックを使って数えます。
+    pub fn depth(&self) -> usize {
+        let mut max = 0;
+        let mut stack = vec![(self, 1)];
+        while let Some((expr, depth)) = stack.pop() {
+            max = max.max(depth);
+            let depth = depth + 1;
+            match expr {
+                Expr::Literal { .. } => {}
+                Expr::Unary { right: expr, .. } | Expr::Grouping { expr, .. } => {
+                    stack.push((expr, depth));
+                }
+                Expr::Binary { left, right, .. } => {
+                    stack.push((left, depth));
+                    stack.push((right, depth));
+                }
+                Expr::Array { elements, .. } => {
+                    stack.extend(elements.iter().map(|element| (element, depth)));
+                }
+                Expr::Map { entries, .. } => {
+                    for (key, value) in entries {
+                        stack.push((key, depth));
+                        stack.push((value, depth));
+                    }
+                }
+                Expr::Index { object, index, .. } => {
+                    stack.push((object, depth));
+                    stack.push((index, depth));
+                }
+                Expr::IndexSet {
+                    object,
+                    index,
+                    value,
+                    ..
+                } => {
+                    stack.push((object, depth));
+                    stack.push((index, depth));
+                    stack.push((value, depth));
+                }
+            }
+        }
+        max
+    }
+
+    /// 式を取り出し、元の位置には同じ行番号の `nil` を残します
+    ///
+    /// `Expr` は `Drop` を実装しているため、パターンで部分式をムーブする代わりに利用します。
+    pub fn take(&mut self) -> Expr {
+        let line = self.line();
+        std::mem::replace(
+            self,
+            Expr::Literal {
+                value: Literal::Nil,
+                line,
+            },
+        )
+    }
+
+    /// 部分式をすべて `stack` に取り出す
+    fn take_children(&mut self, stack: &mut Vec<Expr>) {
+        match self {
+            Expr::Literal { .. } => {}
+            Expr::Unary { right: expr, .. } | Expr::Grouping { expr, .. } => {
+                stack.push(expr.take());
+            }
+            Expr::Binary { left, right, .. } => {
+                stack.push(left.take());
+                stack.push(right.take());
+            }
+            Expr::Array { elements, .. } => stack.append(elements),
+            Expr::Map { entries, .. } => {
+                for (key, value) in entries.drain(..) {
+                    stack.push(key);
+                    stack.push(value);
+                }
+            }
+            Expr::Index { object, index, .. } => {
+                stack.push(object.take());
+                stack.push(index.take());
+            }
+            Expr::IndexSet {
+                object,
+                index,
+                value,
+                ..
+            } => {
+                stack.push(object.take());
+                stack.push(index.take());
+                stack.push(value.take());
+            }
+        }
+    }
+}
+
+/// 深く入れ子になった式を再帰的に破棄するとスタックが溢れるため、明示的なスタックを使って破棄します
+///
+/// 部分式を取り出してから破棄することで、各ノードの `drop` は部分式を持たない状態で呼ばれます。
+impl Drop for Expr {
+    fn drop(&mut self) {
+        let mut stack = vec![];
+        self.take_children(&mut stack);
+        while let Some(mut expr) = stack.pop() {
+            expr.take_children(&mut stack);
+        }
+    }
+}
+
+/// 式に現れるリテラル値を表します
+///
+/// 字句解析の `token::Literal` とは異なり、真偽値と `nil` も値として保持します。
+/// `true`、`false`、`nil` はトークンのリテラル値を持たないため、構文解析器がトークン種別から変換します。
 #[derive(PartialEq, Debug)]
 pub enum Literal {
     Number(f64),
@@ -28,6 +215,7 @@ pub enum BinaryOp {
     Minus,
     Star,
     Slash,
+    Power,
     // 論理演算子
     EqualEqual,
     BangEqual,
@@ -36,3 +224,74 @@ pub enum BinaryOp {
     Less,
     LessEqual,
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::expr::{BinaryOp, Expr, Literal};
+
+    #[test]
+    fn test_unwrap_grouping() {
+        let literal = Expr::Literal {
+            value: Literal::Number(1.0),
+            line: 1,
+        };
+        assert_eq!(&literal, literal.unwrap_grouping());
+
+        let grouping = Expr::Grouping {
+            expr: Box::new(Expr::Grouping {
+                expr: Box::new(Expr::Literal {
+                    value: Literal::Number(1.0),
+                    line: 1,
+                }),
+                line: 1,
+            }),
+            line: 1,
+        };
+        assert_ne!(literal, grouping);
+        assert_eq!(&literal, grouping.unwrap_grouping());
+    }
+
+    #[test]
+    fn test_depth() {
+        let literal = || Expr::Literal {
+            value: Literal::Number(1.0),
+            line: 1,
+        };
+        assert_eq!(1, literal().depth());
+
+        let expr = Expr::Array {
+            elements: vec![
+                literal(),
+                Expr::Grouping {
+                    expr: Box::new(literal()),
+                    line: 1,
+                },
+            ],
+            line: 1,
+        };
+        assert_eq!(3, expr.depth());
+    }
+
+    #[test]
+    fn test_drop_deeply_nested_expr() {
+        // `1 + 1 + 1 + ...` のような左結合の式は左辺に向かって深く入れ子になる
+        let mut expr = Expr::Literal {
+            value: Literal::Number(1.0),
+            line: 1,
+        };
+        for _ in 0..100_000 {
+            expr = Expr::Binary {
+                left: Box::new(expr),
+                op: BinaryOp::Plus,
+                op_line: 1,
+                right: Box::new(Expr::Literal {
+                    value: Literal::Number(1.0),
+                    line: 1,
+                }),
+                line: 1,
+            };
+        }
+
+        drop(expr);
+    }
+}
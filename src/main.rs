@@ -1,16 +1,55 @@
-use std::io;
+use std::{env, io, process};
 
-use crate::repl::run_prompt;
+use crate::{
+    repl::run_prompt,
+    runner::{
+        check_file, diagnostics_json, dump_tokens_json, run_file, run_file_streaming,
+        run_file_timed, should_colorize, EXIT_USAGE,
+    },
+};
 
 pub mod expr;
 pub mod parser;
 pub mod repl;
+pub mod runner;
 pub mod scanner;
 pub mod token;
 
 fn main() {
-    println!("Lox言語のReplです。");
-    println!("コードを記述すれば解析したトークンを出力することが可能です。");
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    // `--no-color` はどのモードとも組み合わせられるため、位置に関係なく取り除いておく
+    let no_color = args.iter().any(|arg| arg == "--no-color");
+    args.retain(|arg| arg != "--no-color");
+    let color = should_colorize(no_color);
 
-    run_prompt(io::stdin(), io::stdout());
+    match args.as_slice() {
+        [] => {
+            println!("Lox言語のReplです。");
+            println!("コードを記述すれば解析したトークンを出力することが可能です。");
+
+            run_prompt(io::stdin().lock(), io::stdout());
+        }
+        [path] if !path.starts_with("--") => {
+            process::exit(run_file(path, color, io::stdout(), io::stderr()));
+        }
+        [flag, path] if flag == "--dump-tokens-json" => {
+            process::exit(dump_tokens_json(path, io::stdout(), io::stderr()));
+        }
+        [flag, path] if flag == "--check" => {
+            process::exit(check_file(path, color, io::stderr()));
+        }
+        [flag, path] if flag == "--diagnostics-json" => {
+            process::exit(diagnostics_json(path, io::stdout(), io::stderr()));
+        }
+        [flag, path] if flag == "--stream" => {
+            process::exit(run_file_streaming(path, color, io::stdout(), io::stderr()));
+        }
+        [flag, path] if flag == "--time" => {
+            process::exit(run_file_timed(path, color, io::stdout(), io::stderr()));
+        }
+        _ => {
+            eprintln!("Usage: rust-template [--no-color] [<file> | --check <file> | --dump-tokens-json <file> | --diagnostics-json <file> | --stream <file> | --time <file>]");
+            process::exit(EXIT_USAGE);
+        }
+    }
 }
@@ -1,5 +1,6 @@
 use crate::{
     expr::{self, BinaryOp, Expr, UnaryOp},
+    scanner::scan_tokens,
     token::{self, Token, TokenType},
 };
 
@@ -7,29 +8,75 @@ use crate::{
 ///
 /// C言語と同じ優先順位と結合度を採用し、以下の式文法に従って解析を進めていく
 ///
-/// * expression -> equality
+/// * expression -> assignment
+/// * assignment -> call "[" expression "]" "=" assignment
+///   | equality ;
 /// * equality   -> comparison ( ("!=" | "==") comparison )* ;
 /// * comparison -> term ( (">" | ">=" | "<" | "<=") term )* ;
 /// * term       -> factor ( ("-" | "+") factor )* ;
 /// * factor     -> unary ( ("/" | "*") unary )* ;
 /// * unary      -> ("!" | "-") unary
-///               | primary ;
+///   | power ;
+/// * power      -> call ( "**" unary )? ;
+/// * call       -> primary ( "[" expression "]" )* ;
 /// * primary    -> Number | String | "true" | "false" | "nil"
-///               | "(" expression ")" ;
+///   | "(" expression ")"
+///   | "[" ( expression ( "," expression )* )? "]"
+///   | "{" ( entry ( "," entry )* )? "}" ;
+/// * entry      -> expression ":" expression ;
+///
+/// `{` はブロックの開始にも利用されるが、式の位置に現れた場合は連想配列リテラルとして解析する
 ///
 pub struct Parser {
     /// `Scanner` によって解析したトークンのシーケンス
     tokens: Vec<Token>,
     /// 次に解析すべきトークン位置
     current: usize,
+    /// 現在解析している式の入れ子の深さ
+    depth: usize,
 }
 
+/// 式の入れ子の深さの上限
+///
+/// 再帰下降構文解析では入れ子の深さだけ関数呼び出しが積み重なるため、
+/// 任意のトークン列を与えられてもスタックオーバーフローしないように上限を設ける
+const MAX_DEPTH: usize = 128;
+
 /// 構文解析エラーを表すカスタムエラー型です。
 ///
 /// このエラーは、解析中に発生した特定の問題を表すために使用されます。
-/// `String`はエラーメッセージを保持します。
+/// 複数のエラーを行番号順に並べて報告できるように、エラーが発生した行番号と列番号を保持します。
 #[derive(PartialEq, Debug)]
-pub struct ParserError(String);
+pub struct ParserError {
+    /// エラーが発生したトークンの行番号
+    pub line: usize,
+    /// エラーが発生したトークンが行の何文字目に当たるか (1始まり)
+    ///
+    /// 字句解析を経由せずに生成した `Token::synthetic` のトークンでは `0` となります。
+    pub column: usize,
+    /// エラーメッセージ
+    pub message: String,
+}
+
+impl ParserError {
+    fn new(line: usize, column: usize, message: impl Into<String>) -> Self {
+        ParserError {
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+
+    /// `token` の位置で発生したエラーを生成します。
+    fn at(token: &Token, message: &str) -> Self {
+        let location = match token.ty {
+            TokenType::Eof => "at end".to_string(),
+            TokenType::Newline => "at newline".to_string(),
+            _ => format!("at '{}'", token.lexeme.iter().collect::<String>()),
+        };
+        ParserError::new(token.line, token.column, format!("{location}, {message}"))
+    }
+}
 
 impl std::error::Error for ParserError {}
 
@@ -39,25 +86,106 @@ impl std::error::Error for ParserError {}
 /// デバッグやエラーログに役立ちます。
 impl std::fmt::Display for ParserError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "ParserError: {}", self.0)
+        write!(f, "[line {}] ParserError: {}", self.line, self.message)
     }
 }
 
+/// 文字列を字句解析し、1つの式として構文解析します。
+///
+/// `scan_tokens` の結果を `Parser::new` に渡す手順をまとめたものです。
+/// 字句解析のエラーは、同じ行番号とメッセージを持つ `ParserError` として返します。
+pub fn parse_expression(input: &str) -> Result<Expr, ParserError> {
+    let tokens =
+        scan_tokens(input).map_err(|err| ParserError::new(err.line, err.column, err.message))?;
+    Parser::new(tokens).parse_to_end()
+}
+
 impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Self {
-        Parser { tokens, current: 0 }
+    pub fn new(mut tokens: Vec<Token>) -> Self {
+        // 末尾に `Eof` を持たないトークン列が渡された場合でもパニックせずに解析できるよう、
+        // 合成した `Eof` トークンを末尾に追加しておく
+        if !matches!(tokens.last(), Some(token) if token.ty == TokenType::Eof) {
+            let line = tokens.last().map_or(1, |token| token.line);
+            tokens.push(Token::synthetic(TokenType::Eof, "", line));
+        }
+
+        Parser {
+            tokens,
+            current: 0,
+            depth: 0,
+        }
+    }
+
+    /// 構文解析器が保持しているトークン列を取り出します。
+    ///
+    /// `new` で末尾に `Eof` が追加された場合は、そのトークンも含みます。
+    pub fn into_tokens(self) -> Vec<Token> {
+        self.tokens
+    }
+
+    /// まだ消費していないトークン列を返します。末尾の `Eof` も含みます。
+    ///
+    /// `parse` は1つの式を解析した時点で終了するため、後続のトークンを別の方法で処理する場合に利用します。
+    /// 後続のトークンをエラーとする場合は `parse_to_end` を利用してください。
+    pub fn remaining(&self) -> &[Token] {
+        &self.tokens[self.current..]
     }
 
     pub fn parse(&mut self) -> Result<Expr, ParserError> {
-        self.expression().map_err(|e| {
+        self.expression().inspect_err(|_| {
             self.synchronize();
-            e
         })
     }
 
-    // expression -> equality
+    /// 1つの式を解析し、その後に `Eof` 以外のトークンが残っていればエラーとします。
+    ///
+    /// `1 2` のように式の後に続くトークンを黙って無視しないよう、入力全体を1つの式として扱う場合に利用します。
+    pub fn parse_to_end(&mut self) -> Result<Expr, ParserError> {
+        let expr = self.parse()?;
+        if !self.is_at_end() {
+            return Err(ParserError::at(self.peek(), "Expect end of expression"));
+        }
+        Ok(expr)
+    }
+
+    // expression -> assignment
     fn expression(&mut self) -> Result<Expr, ParserError> {
-        self.equality()
+        self.assignment()
+    }
+
+    // assignment -> call "[" expression "]" "=" assignment
+    //             | equality ;
+    //
+    // 代入先は `=` を見つけるまで判別できないため、一度通常の式として解析してから
+    // 代入可能な式であるかどうかを確認する
+    fn assignment(&mut self) -> Result<Expr, ParserError> {
+        let expr = self.equality()?;
+
+        if self.matches(&[TokenType::Equal]) {
+            let equals = self.current - 1;
+            // 右辺の代入は入れ子として数え、`a = b = c = ...` の連鎖でもスタックが溢れないようにする
+            let value = self.nested(Self::assignment)?;
+
+            let mut expr = expr;
+            return match &mut expr {
+                Expr::Index {
+                    object,
+                    index,
+                    line,
+                } => Ok(Expr::IndexSet {
+                    object: Box::new(object.take()),
+                    index: Box::new(index.take()),
+                    value: Box::new(value),
+                    line: *line,
+                }),
+                _ => Err(ParserError::at(
+                    &self.tokens[equals],
+                    "Invalid assignment target",
+                )),
+            };
+        }
+
+        Ok(expr)
     }
 
     // equality   -> comparison ( ("!=" | "==") comparison )* ;
@@ -66,9 +194,16 @@ impl Parser {
 
         while self.matches(&[TokenType::BangEqual, TokenType::EqualEqual]) {
             let operator = self.previous();
+            let op_line = operator.line;
             let binary_op = parse_binary_op(operator)?;
             let right = self.comparison()?;
-            expr = Expr::Binary(Box::new(expr), binary_op, Box::new(right));
+            expr = Expr::Binary {
+                line: expr.line(),
+                left: Box::new(expr),
+                op: binary_op,
+                op_line,
+                right: Box::new(right),
+            };
         }
 
         Ok(expr)
@@ -85,9 +220,16 @@ impl Parser {
             TokenType::LessEqual,
         ]) {
             let operator = self.previous();
+            let op_line = operator.line;
             let binary_op = parse_binary_op(operator)?;
             let right = self.term()?;
-            expr = Expr::Binary(Box::new(expr), binary_op, Box::new(right));
+            expr = Expr::Binary {
+                line: expr.line(),
+                left: Box::new(expr),
+                op: binary_op,
+                op_line,
+                right: Box::new(right),
+            };
         }
 
         Ok(expr)
@@ -99,9 +241,16 @@ impl Parser {
 
         while self.matches(&[TokenType::Minus, TokenType::Plus]) {
             let operator = self.previous();
+            let op_line = operator.line;
             let binary_op = parse_binary_op(operator)?;
             let right = self.factor()?;
-            expr = Expr::Binary(Box::new(expr), binary_op, Box::new(right));
+            expr = Expr::Binary {
+                line: expr.line(),
+                left: Box::new(expr),
+                op: binary_op,
+                op_line,
+                right: Box::new(right),
+            };
         }
 
         Ok(expr)
@@ -113,75 +262,212 @@ impl Parser {
 
         while self.matches(&[TokenType::Slash, TokenType::Star]) {
             let operator = self.previous();
+            let op_line = operator.line;
             let binary_op = parse_binary_op(operator)?;
             let right = self.unary()?;
-            expr = Expr::Binary(Box::new(expr), binary_op, Box::new(right));
+            expr = Expr::Binary {
+                line: expr.line(),
+                left: Box::new(expr),
+                op: binary_op,
+                op_line,
+                right: Box::new(right),
+            };
         }
 
         Ok(expr)
     }
 
     // unary      -> ("!" | "-") unary
-    //             | primary ;
+    //             | power ;
+    //
+    // 代入の右辺を除く式の入れ子は必ず `unary` を経由するため、ここで入れ子の深さを制限する
     fn unary(&mut self) -> Result<Expr, ParserError> {
+        self.nested(Self::unary_or_power)
+    }
+
+    /// 入れ子の深さを1つ増やして `parse` を呼び出す
+    ///
+    /// 深さが `MAX_DEPTH` に達している場合は、呼び出さずにエラーを返す
+    fn nested(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<Expr, ParserError>,
+    ) -> Result<Expr, ParserError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(ParserError::at(
+                self.peek(),
+                &format!("Expression nesting exceeds {MAX_DEPTH} levels"),
+            ));
+        }
+
+        self.depth += 1;
+        let expr = parse(self);
+        self.depth -= 1;
+        expr
+    }
+
+    fn unary_or_power(&mut self) -> Result<Expr, ParserError> {
         if self.matches(&[TokenType::Bang, TokenType::Minus]) {
             let operator = self.previous();
+            let line = operator.line;
             let unary_op = parse_unary_op(operator)?;
             let right = self.unary()?;
-            return Ok(Expr::Unary(unary_op, Box::new(right)));
+            return Ok(Expr::Unary {
+                op: unary_op,
+                right: Box::new(right),
+                line,
+            });
+        }
+        // Lox には単項の `+` がないため、汎用的な "Expect expression" より具体的なエラーを返す
+        if self.check(&TokenType::Plus) {
+            return Err(ParserError::at(self.peek(), "Unary '+' is not supported"));
+        }
+
+        self.power()
+    }
+
+    // power      -> call ( "**" unary )? ;
+    //
+    // 右辺を `unary` として再帰的に解析することで右結合を実現する
+    // 例えば `2 ** 3 ** 2` は `2 ** (3 ** 2)` として解析される
+    fn power(&mut self) -> Result<Expr, ParserError> {
+        let expr = self.call()?;
+
+        if self.matches(&[TokenType::StarStar]) {
+            let operator = self.previous();
+            let op_line = operator.line;
+            let binary_op = parse_binary_op(operator)?;
+            let right = self.unary()?;
+            return Ok(Expr::Binary {
+                line: expr.line(),
+                left: Box::new(expr),
+                op: binary_op,
+                op_line,
+                right: Box::new(right),
+            });
+        }
+
+        Ok(expr)
+    }
+
+    // call       -> primary ( "[" expression "]" )* ;
+    fn call(&mut self) -> Result<Expr, ParserError> {
+        let mut expr = self.primary()?;
+
+        while self.matches(&[TokenType::LBracket]) {
+            let index = self.expression()?;
+            self.consume(TokenType::RBracket, "expect ']' after index")?;
+            expr = Expr::Index {
+                line: expr.line(),
+                object: Box::new(expr),
+                index: Box::new(index),
+            };
         }
 
-        self.primary()
+        Ok(expr)
     }
 
     // primary    -> Number | String | "true" | "false" | "nil"
-    //             | "(" expression ")" ;
+    //             | "(" expression ")"
+    //             | "[" ( expression ( "," expression )* )? "]"
+    //             | "{" ( entry ( "," entry )* )? "}" ;
     fn primary(&mut self) -> Result<Expr, ParserError> {
+        let line = self.peek().line;
+
         if self.matches(&[TokenType::False]) {
-            return Ok(Expr::Literal(expr::Literal::False));
+            return Ok(Expr::Literal {
+                value: expr::Literal::False,
+                line,
+            });
         }
         if self.matches(&[TokenType::True]) {
-            return Ok(Expr::Literal(expr::Literal::True));
+            return Ok(Expr::Literal {
+                value: expr::Literal::True,
+                line,
+            });
         }
         if self.matches(&[TokenType::Nil]) {
-            return Ok(Expr::Literal(expr::Literal::Nil));
+            return Ok(Expr::Literal {
+                value: expr::Literal::Nil,
+                line,
+            });
         }
         if self.matches(&[TokenType::Number]) {
             if let Some(token::Literal::Number(value)) = self.previous().literal {
-                return Ok(Expr::Literal(expr::Literal::Number(value)));
+                return Ok(Expr::Literal {
+                    value: expr::Literal::Number(value),
+                    line,
+                });
             } else {
-                return Err(ParserError(
-                    "parser found Number Literal Token, but literal is not f64 values".to_string(),
+                return Err(ParserError::at(
+                    self.previous(),
+                    "parser found Number Literal Token, but literal is not f64 values",
                 ));
             }
         }
         if self.matches(&[TokenType::String]) {
             if let Some(token::Literal::Str(value)) = &self.previous().literal {
-                return Ok(Expr::Literal(expr::Literal::String(value.to_owned())));
+                return Ok(Expr::Literal {
+                    value: expr::Literal::String(value.to_owned()),
+                    line,
+                });
             } else {
-                return Err(ParserError(
-                    "parser found String Literal Token, but literal is not String values"
-                        .to_string(),
+                return Err(ParserError::at(
+                    self.previous(),
+                    "parser found String Literal Token, but literal is not String values",
                 ));
             }
         }
         if self.matches(&[TokenType::LParan]) {
+            // `()` を汎用的な "Expect expression" ではなく、空の括弧であると分かるエラーにする
+            if self.check(&TokenType::RParan) {
+                return Err(ParserError::at(
+                    self.peek(),
+                    "Empty parentheses are not a valid expression",
+                ));
+            }
             let expr = self.expression()?;
             self.consume(TokenType::RParan, "expect ')' after expression")?;
-            return Ok(Expr::Grouping(Box::new(expr)));
+            return Ok(Expr::Grouping {
+                expr: Box::new(expr),
+                line,
+            });
         }
-
-        let next_token = self.peek();
-        match next_token.ty {
-            TokenType::Eof => Err(ParserError(format!(
-                "token line {}, lexeme: {:?}, error {}",
-                next_token.line, next_token.lexeme, "Expect expression"
-            ))),
-            _ => Err(ParserError(format!(
-                "token line {} at end, error {}",
-                next_token.line, "Expect expression"
-            ))),
+        if self.matches(&[TokenType::LBracket]) {
+            let mut elements = vec![];
+            if !self.check(&TokenType::RBracket) {
+                loop {
+                    elements.push(self.expression()?);
+                    if !self.matches(&[TokenType::Comma]) {
+                        break;
+                    }
+                }
+            }
+            self.consume(TokenType::RBracket, "expect ']' after array elements")?;
+            return Ok(Expr::Array { elements, line });
+        }
+        if self.matches(&[TokenType::LBrace]) {
+            let mut entries = vec![];
+            if !self.check(&TokenType::RBrace) {
+                loop {
+                    entries.push(self.entry()?);
+                    if !self.matches(&[TokenType::Comma]) {
+                        break;
+                    }
+                }
+            }
+            self.consume(TokenType::RBrace, "expect '}' after map entries")?;
+            return Ok(Expr::Map { entries, line });
         }
+
+        Err(ParserError::at(self.peek(), "Expect expression"))
+    }
+
+    // entry      -> expression ":" expression ;
+    fn entry(&mut self) -> Result<(Expr, Expr), ParserError> {
+        let key = self.expression()?;
+        self.consume(TokenType::Colon, "expect ':' after map key")?;
+        let value = self.expression()?;
+        Ok((key, value))
     }
 
     fn matches(&mut self, types: &[TokenType]) -> bool {
@@ -198,7 +484,7 @@ impl Parser {
         if self.is_at_end() {
             return false;
         }
-        return self.peek().ty == *ty;
+        self.peek().ty == *ty
     }
 
     fn advance(&mut self) -> &Token {
@@ -208,12 +494,23 @@ impl Parser {
         self.previous()
     }
 
+    /// 次に解析すべきトークンを返す
+    ///
+    /// `Parser::new` でトークン列の末尾に `Eof` があることを保証しており、
+    /// `advance` は `Eof` より先へ進まないため、範囲外を参照することはない
     fn peek(&self) -> &Token {
-        self.tokens.get(self.current).unwrap()
+        self.tokens
+            .get(self.current)
+            .expect("token stream should always end with Eof")
     }
 
+    /// 直前に消費したトークンを返す
+    ///
+    /// まだ何も消費していない場合は、先頭のトークンを返す
     fn previous(&self) -> &Token {
-        self.tokens.get(self.current - 1).unwrap()
+        self.tokens
+            .get(self.current.saturating_sub(1))
+            .expect("token stream should always end with Eof")
     }
 
     fn is_at_end(&self) -> bool {
@@ -225,17 +522,7 @@ impl Parser {
             return Ok(self.advance());
         }
 
-        let next_token = self.peek();
-        match next_token.ty {
-            TokenType::Eof => Err(ParserError(format!(
-                "token line {}, lexeme: {:?}, error {}",
-                next_token.line, next_token.lexeme, message
-            ))),
-            _ => Err(ParserError(format!(
-                "token line {} at end, error {}",
-                next_token.line, message
-            ))),
-        }
+        Err(ParserError::at(self.peek(), message))
     }
 
     fn synchronize(&mut self) {
@@ -269,6 +556,7 @@ fn parse_binary_op(token: &Token) -> Result<BinaryOp, ParserError> {
         TokenType::Minus => BinaryOp::Minus,
         TokenType::Star => BinaryOp::Star,
         TokenType::Slash => BinaryOp::Slash,
+        TokenType::StarStar => BinaryOp::Power,
         // 論理演算子
         TokenType::EqualEqual => BinaryOp::EqualEqual,
         TokenType::BangEqual => BinaryOp::BangEqual,
@@ -276,7 +564,7 @@ fn parse_binary_op(token: &Token) -> Result<BinaryOp, ParserError> {
         TokenType::GreaterEqual => BinaryOp::GreaterEqual,
         TokenType::Less => BinaryOp::Less,
         TokenType::LessEqual => BinaryOp::LessEqual,
-        _ => return Err(ParserError("should be binaryOp".to_string())),
+        _ => return Err(ParserError::at(token, "should be binaryOp")),
     };
 
     Ok(binary_op)
@@ -286,7 +574,7 @@ fn parse_unary_op(token: &Token) -> Result<UnaryOp, ParserError> {
     let unary_op = match token.ty {
         TokenType::Bang => UnaryOp::Bang,
         TokenType::Minus => UnaryOp::Minus,
-        _ => return Err(ParserError("should be unaryOp".to_string())),
+        _ => return Err(ParserError::at(token, "should be unaryOp")),
     };
 
     Ok(unary_op)
@@ -296,7 +584,7 @@ fn parse_unary_op(token: &Token) -> Result<UnaryOp, ParserError> {
 mod tests {
     use crate::{
         expr::{BinaryOp, Expr, Literal, UnaryOp},
-        parser::{parse_binary_op, ParserError},
+        parser::{parse_binary_op, parse_expression, ParserError},
         scanner::scan_tokens,
         token::{Token, TokenType},
     };
@@ -310,6 +598,8 @@ mod tests {
             lexeme: vec!['-'],
             literal: None,
             line: 1,
+            column: 1,
+            offset: 0,
         })
         .expect("Failed to parse Token");
         assert_eq!(UnaryOp::Minus, unary_op);
@@ -319,9 +609,11 @@ mod tests {
             lexeme: vec!['+'],
             literal: None,
             line: 1,
+            column: 1,
+            offset: 0,
         })
         .expect_err("Unexpectedly Success to parse Token");
-        assert_eq!(ParserError(format!("should be unaryOp")), error);
+        assert_eq!(ParserError::new(1, 1, "at '+', should be unaryOp"), error);
     }
 
     #[test]
@@ -331,6 +623,8 @@ mod tests {
             lexeme: vec!['=', '='],
             literal: None,
             line: 1,
+            column: 1,
+            offset: 0,
         })
         .expect("Failed to parse Token");
         assert_eq!(BinaryOp::EqualEqual, binary_op);
@@ -340,9 +634,11 @@ mod tests {
             lexeme: vec!['!'],
             literal: None,
             line: 1,
+            column: 1,
+            offset: 0,
         })
         .expect_err("Unexpectedly Success to parse Token");
-        assert_eq!(ParserError(format!("should be binaryOp")), error);
+        assert_eq!(ParserError::new(1, 1, "at '!', should be binaryOp"), error);
     }
 
     #[test]
@@ -355,12 +651,555 @@ mod tests {
         let expr = Parser::new(tokens).parse().expect("Failed to parse Tokens");
 
         assert_eq!(
-            Expr::Binary(
-                Box::new(Expr::Literal(Literal::Number(2.0))),
-                BinaryOp::Plus,
-                Box::new(Expr::Literal(Literal::Number(3.0))),
-            ),
+            Expr::Binary {
+                left: Box::new(Expr::Literal {
+                    value: Literal::Number(2.0),
+                    line: 1,
+                }),
+                op: BinaryOp::Plus,
+                op_line: 1,
+                right: Box::new(Expr::Literal {
+                    value: Literal::Number(3.0),
+                    line: 1,
+                }),
+                line: 1,
+            },
+            expr
+        );
+    }
+
+    #[test]
+    fn test_power_is_right_associative() {
+        let input = "2 ** 3 ** 2";
+
+        let tokens = scan_tokens(input).expect("Failed to scan input string");
+        let expr = Parser::new(tokens).parse().expect("Failed to parse Tokens");
+
+        assert_eq!(
+            Expr::Binary {
+                left: Box::new(Expr::Literal {
+                    value: Literal::Number(2.0),
+                    line: 1,
+                }),
+                op: BinaryOp::Power,
+                op_line: 1,
+                right: Box::new(Expr::Binary {
+                    left: Box::new(Expr::Literal {
+                        value: Literal::Number(3.0),
+                        line: 1,
+                    }),
+                    op: BinaryOp::Power,
+                    op_line: 1,
+                    right: Box::new(Expr::Literal {
+                        value: Literal::Number(2.0),
+                        line: 1,
+                    }),
+                    line: 1,
+                }),
+                line: 1,
+            },
+            expr
+        );
+    }
+
+    #[test]
+    fn test_parse_tokens_without_eof() {
+        let error = Parser::new(vec![])
+            .parse()
+            .expect_err("Unexpectedly Success to parse empty tokens");
+        assert_eq!(ParserError::new(1, 0, "at end, Expect expression"), error);
+
+        let expr = Parser::new(vec![Token {
+            ty: TokenType::Number,
+            lexeme: vec!['1'],
+            literal: Some(crate::token::Literal::Number(1.0)),
+            line: 1,
+            column: 1,
+            offset: 0,
+        }])
+        .parse()
+        .expect("Failed to parse Tokens");
+        assert_eq!(
+            Expr::Literal {
+                value: Literal::Number(1.0),
+                line: 1,
+            },
+            expr
+        );
+    }
+
+    #[test]
+    fn test_array_literal() {
+        let tokens = scan_tokens("[]").expect("Failed to scan input string");
+        let expr = Parser::new(tokens).parse().expect("Failed to parse Tokens");
+        assert_eq!(
+            Expr::Array {
+                elements: vec![],
+                line: 1,
+            },
+            expr
+        );
+
+        let tokens = scan_tokens("[1, \"two\", 1 + 2]").expect("Failed to scan input string");
+        let expr = Parser::new(tokens).parse().expect("Failed to parse Tokens");
+        assert_eq!(
+            Expr::Array {
+                elements: vec![
+                    Expr::Literal {
+                        value: Literal::Number(1.0),
+                        line: 1,
+                    },
+                    Expr::Literal {
+                        value: Literal::String("two".to_string()),
+                        line: 1,
+                    },
+                    Expr::Binary {
+                        left: Box::new(Expr::Literal {
+                            value: Literal::Number(1.0),
+                            line: 1,
+                        }),
+                        op: BinaryOp::Plus,
+                        op_line: 1,
+                        right: Box::new(Expr::Literal {
+                            value: Literal::Number(2.0),
+                            line: 1,
+                        }),
+                        line: 1,
+                    },
+                ],
+                line: 1,
+            },
+            expr
+        );
+    }
+
+    #[test]
+    fn test_index() {
+        let tokens = scan_tokens("[1, 2][0][1]").expect("Failed to scan input string");
+        let expr = Parser::new(tokens).parse().expect("Failed to parse Tokens");
+
+        assert_eq!(
+            Expr::Index {
+                object: Box::new(Expr::Index {
+                    object: Box::new(Expr::Array {
+                        elements: vec![
+                            Expr::Literal {
+                                value: Literal::Number(1.0),
+                                line: 1,
+                            },
+                            Expr::Literal {
+                                value: Literal::Number(2.0),
+                                line: 1,
+                            },
+                        ],
+                        line: 1,
+                    }),
+                    index: Box::new(Expr::Literal {
+                        value: Literal::Number(0.0),
+                        line: 1,
+                    }),
+                    line: 1,
+                }),
+                index: Box::new(Expr::Literal {
+                    value: Literal::Number(1.0),
+                    line: 1,
+                }),
+                line: 1,
+            },
+            expr
+        );
+    }
+
+    #[test]
+    fn test_index_binds_tighter_than_arithmetic() {
+        // 変数がまだないため、`arr[i] + 1` の代わりに配列リテラルを添字アクセスする
+        let tokens = scan_tokens("[1][0] + 1").expect("Failed to scan input string");
+        let expr = Parser::new(tokens).parse().expect("Failed to parse Tokens");
+        assert_eq!(
+            Expr::Binary {
+                left: Box::new(Expr::Index {
+                    object: Box::new(Expr::Array {
+                        elements: vec![Expr::Literal {
+                            value: Literal::Number(1.0),
+                            line: 1,
+                        }],
+                        line: 1,
+                    }),
+                    index: Box::new(Expr::Literal {
+                        value: Literal::Number(0.0),
+                        line: 1,
+                    }),
+                    line: 1,
+                }),
+                op: BinaryOp::Plus,
+                op_line: 1,
+                right: Box::new(Expr::Literal {
+                    value: Literal::Number(1.0),
+                    line: 1,
+                }),
+                line: 1,
+            },
+            expr
+        );
+
+        // `a + b[c]` に相当する式
+        let tokens = scan_tokens("1 + [2][0]").expect("Failed to scan input string");
+        let expr = Parser::new(tokens).parse().expect("Failed to parse Tokens");
+        assert_eq!(
+            Expr::Binary {
+                left: Box::new(Expr::Literal {
+                    value: Literal::Number(1.0),
+                    line: 1,
+                }),
+                op: BinaryOp::Plus,
+                op_line: 1,
+                right: Box::new(Expr::Index {
+                    object: Box::new(Expr::Array {
+                        elements: vec![Expr::Literal {
+                            value: Literal::Number(2.0),
+                            line: 1,
+                        }],
+                        line: 1,
+                    }),
+                    index: Box::new(Expr::Literal {
+                        value: Literal::Number(0.0),
+                        line: 1,
+                    }),
+                    line: 1,
+                }),
+                line: 1,
+            },
+            expr
+        );
+    }
+
+    #[test]
+    fn test_index_assignment() {
+        let tokens = scan_tokens("[1, 2][0] = 42").expect("Failed to scan input string");
+        let expr = Parser::new(tokens).parse().expect("Failed to parse Tokens");
+
+        assert_eq!(
+            Expr::IndexSet {
+                object: Box::new(Expr::Array {
+                    elements: vec![
+                        Expr::Literal {
+                            value: Literal::Number(1.0),
+                            line: 1,
+                        },
+                        Expr::Literal {
+                            value: Literal::Number(2.0),
+                            line: 1,
+                        },
+                    ],
+                    line: 1,
+                }),
+                index: Box::new(Expr::Literal {
+                    value: Literal::Number(0.0),
+                    line: 1,
+                }),
+                value: Box::new(Expr::Literal {
+                    value: Literal::Number(42.0),
+                    line: 1,
+                }),
+                line: 1,
+            },
+            expr
+        );
+
+        let tokens = scan_tokens("1 = 2").expect("Failed to scan input string");
+        let error = Parser::new(tokens)
+            .parse()
+            .expect_err("Unexpectedly Success to parse Tokens");
+        assert_eq!(
+            ParserError::new(1, 3, "at '=', Invalid assignment target"),
+            error
+        );
+    }
+
+    #[test]
+    fn test_map_literal() {
+        let tokens =
+            scan_tokens("{ \"one\": 1, 2: \"two\" }").expect("Failed to scan input string");
+        let expr = Parser::new(tokens).parse().expect("Failed to parse Tokens");
+
+        assert_eq!(
+            Expr::Map {
+                entries: vec![
+                    (
+                        Expr::Literal {
+                            value: Literal::String("one".to_string()),
+                            line: 1,
+                        },
+                        Expr::Literal {
+                            value: Literal::Number(1.0),
+                            line: 1,
+                        },
+                    ),
+                    (
+                        Expr::Literal {
+                            value: Literal::Number(2.0),
+                            line: 1,
+                        },
+                        Expr::Literal {
+                            value: Literal::String("two".to_string()),
+                            line: 1,
+                        },
+                    ),
+                ],
+                line: 1,
+            },
+            expr
+        );
+
+        let tokens = scan_tokens("{ \"one\": 1 }[\"one\"]").expect("Failed to scan input string");
+        let expr = Parser::new(tokens).parse().expect("Failed to parse Tokens");
+
+        assert_eq!(
+            Expr::Index {
+                object: Box::new(Expr::Map {
+                    entries: vec![(
+                        Expr::Literal {
+                            value: Literal::String("one".to_string()),
+                            line: 1,
+                        },
+                        Expr::Literal {
+                            value: Literal::Number(1.0),
+                            line: 1,
+                        },
+                    )],
+                    line: 1,
+                }),
+                index: Box::new(Expr::Literal {
+                    value: Literal::String("one".to_string()),
+                    line: 1,
+                }),
+                line: 1,
+            },
             expr
         );
     }
+
+    #[test]
+    fn test_expr_line() {
+        let input = r#"
+        1 +
+        2
+        * 3
+        "#;
+
+        let tokens = scan_tokens(input).expect("Failed to scan input string");
+        let expr = Parser::new(tokens).parse().expect("Failed to parse Tokens");
+        assert_eq!(2, expr.line());
+
+        let Expr::Binary { op_line, right, .. } = &expr else {
+            panic!("expression should be binary: {expr:?}");
+        };
+        assert_eq!(2, *op_line);
+        assert_eq!(3, right.line());
+
+        let Expr::Binary { op_line, .. } = right.as_ref() else {
+            panic!("expression should be binary: {right:?}");
+        };
+        assert_eq!(4, *op_line);
+    }
+
+    #[test]
+    fn test_keyword_literals() {
+        for (input, expected) in [
+            ("true", Literal::True),
+            ("false", Literal::False),
+            ("nil", Literal::Nil),
+        ] {
+            let tokens = scan_tokens(input).expect("Failed to scan input string");
+            assert_eq!(None, tokens[0].literal);
+
+            let expr = Parser::new(tokens).parse().expect("Failed to parse Tokens");
+            assert_eq!(
+                Expr::Literal {
+                    value: expected,
+                    line: 1,
+                },
+                expr
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_to_end() {
+        let tokens = scan_tokens("1 2").expect("Failed to scan input string");
+        let error = Parser::new(tokens)
+            .parse_to_end()
+            .expect_err("Unexpectedly Success to parse Tokens");
+        assert_eq!(
+            ParserError::new(1, 3, "at '2', Expect end of expression"),
+            error
+        );
+
+        let error = parse_expression("1 + 2 )").expect_err("Unexpectedly Success to parse input");
+        assert_eq!(
+            ParserError::new(1, 7, "at ')', Expect end of expression"),
+            error
+        );
+
+        parse_expression("(1 + 2)").expect("Failed to parse input string");
+    }
+
+    #[test]
+    fn test_remaining_tokens() {
+        let tokens = scan_tokens("1 + 2 ) 3").expect("Failed to scan input string");
+        let mut parser = Parser::new(tokens);
+        assert_eq!(6, parser.remaining().len());
+
+        parser.parse().expect("Failed to parse Tokens");
+        assert_eq!(
+            vec![&TokenType::RParan, &TokenType::Number, &TokenType::Eof],
+            parser
+                .remaining()
+                .iter()
+                .map(|token| &token.ty)
+                .collect::<Vec<_>>()
+        );
+
+        let tokens = parser.into_tokens();
+        assert_eq!(6, tokens.len());
+        assert_eq!(TokenType::Plus, tokens[1].ty);
+    }
+
+    #[test]
+    fn test_parse_expression() {
+        let expr = parse_expression("-1").expect("Failed to parse input string");
+        assert_eq!(
+            Expr::Unary {
+                op: UnaryOp::Minus,
+                right: Box::new(Expr::Literal {
+                    value: Literal::Number(1.0),
+                    line: 1,
+                }),
+                line: 1,
+            },
+            expr
+        );
+
+        let error = parse_expression("1 +\n@").expect_err("Unexpectedly Success to parse input");
+        assert_eq!(ParserError::new(2, 1, "invalid token: @"), error);
+
+        let error = parse_expression("(1").expect_err("Unexpectedly Success to parse input");
+        assert_eq!(
+            ParserError::new(1, 3, "at end, expect ')' after expression"),
+            error
+        );
+    }
+
+    #[test]
+    fn test_empty_grouping() {
+        let tokens = scan_tokens("1 + ()").expect("Failed to scan input string");
+        let error = Parser::new(tokens)
+            .parse()
+            .expect_err("Unexpectedly Success to parse empty parentheses");
+        assert_eq!(
+            ParserError::new(1, 6, "at ')', Empty parentheses are not a valid expression"),
+            error
+        );
+
+        let tokens = scan_tokens("(1)").expect("Failed to scan input string");
+        let expr = Parser::new(tokens).parse().expect("Failed to parse Tokens");
+        assert_eq!(
+            Expr::Grouping {
+                expr: Box::new(Expr::Literal {
+                    value: Literal::Number(1.0),
+                    line: 1,
+                }),
+                line: 1,
+            },
+            expr
+        );
+    }
+
+    #[test]
+    fn test_unary_plus() {
+        let error = parse_expression("+5").expect_err("Unexpectedly Success to parse input");
+        assert_eq!(
+            ParserError::new(1, 1, "at '+', Unary '+' is not supported"),
+            error
+        );
+
+        let error = parse_expression("1 * +5").expect_err("Unexpectedly Success to parse input");
+        assert_eq!(
+            ParserError::new(1, 5, "at '+', Unary '+' is not supported"),
+            error
+        );
+        parse_expression("1 + 5").expect("Failed to parse input string");
+    }
+
+    #[test]
+    fn test_parse_arbitrary_tokens() {
+        let error = Parser::new(vec![Token {
+            ty: TokenType::RParan,
+            lexeme: vec![')'],
+            literal: None,
+            line: 1,
+            column: 1,
+            offset: 0,
+        }])
+        .parse()
+        .expect_err("Unexpectedly Success to parse Tokens");
+        assert_eq!(ParserError::new(1, 1, "at ')', Expect expression"), error);
+
+        let error = Parser::new(vec![Token {
+            ty: TokenType::Number,
+            lexeme: vec!['1'],
+            literal: None,
+            line: 1,
+            column: 1,
+            offset: 0,
+        }])
+        .parse()
+        .expect_err("Unexpectedly Success to parse Tokens");
+        assert_eq!(
+            ParserError::new(
+                1,
+                1,
+                "at '1', parser found Number Literal Token, but literal is not f64 values"
+            ),
+            error
+        );
+    }
+
+    #[test]
+    fn test_parse_deeply_nested_expression() {
+        let input = "(".repeat(100) + "1" + &")".repeat(100);
+        let tokens = scan_tokens(&input).expect("Failed to scan input string");
+        Parser::new(tokens).parse().expect("Failed to parse Tokens");
+
+        let input = "-".repeat(10_000) + "1";
+        let tokens = scan_tokens(&input).expect("Failed to scan input string");
+        let error = Parser::new(tokens)
+            .parse()
+            .expect_err("Unexpectedly Success to parse Tokens");
+        assert_eq!(
+            ParserError::new(1, 129, "at '-', Expression nesting exceeds 128 levels"),
+            error
+        );
+    }
+
+    #[test]
+    fn test_parse_long_assignment_chain() {
+        let input = "[1][0] = ".repeat(100) + "1";
+        let tokens = scan_tokens(&input).expect("Failed to scan input string");
+        Parser::new(tokens).parse().expect("Failed to parse Tokens");
+
+        for input in [
+            "1 = ".repeat(200_000) + "1",
+            "[1][0] = ".repeat(100_000) + "1",
+        ] {
+            let tokens = scan_tokens(&input).expect("Failed to scan input string");
+            let error = Parser::new(tokens)
+                .parse()
+                .expect_err("Unexpectedly Success to parse Tokens");
+            assert_eq!(
+                "at '1', Expression nesting exceeds 128 levels",
+                error.message
+            );
+        }
+    }
 }
@@ -1,17 +1,42 @@
-use std::io::{Stdin, Stdout, Write};
+use std::io::{BufRead, Write};
 
-use crate::{parser::Parser, scanner::scan_tokens, token::TokenType};
+use crate::{
+    parser::Parser,
+    runner::format_expression,
+    scanner::{scan_tokens, tokenize_expr, ExprStatus},
+    token::TokenType,
+};
+
+pub fn run_prompt(mut stdin: impl BufRead, mut stdout: impl Write) {
+    let mut input = String::new();
 
-pub fn run_prompt(stdin: Stdin, mut stdout: Stdout) {
     loop {
-        write!(stdout, ">> ").expect("should have written prompt string >>");
+        // 式の途中であれば継続行であることが分かるプロンプトを表示する
+        let prompt = if input.is_empty() { ">> " } else { ".. " };
+        write!(stdout, "{prompt}").expect("should have written prompt string");
         stdout.flush().expect("should have flushed stdout!");
 
-        let mut input = String::new();
-        if let Err(e) = stdin.read_line(&mut input) {
-            write!(stdout, "Error: {e}").expect("should have written error message");
-            return;
+        let at_eof = match stdin.read_line(&mut input) {
+            Ok(read) => read == 0,
+            Err(e) => {
+                write!(stdout, "Error: {e}").expect("should have written error message");
+                return;
+            }
+        };
+
+        match tokenize_expr(&input) {
+            ExprStatus::Empty if at_eof => return,
+            // 空白やコメントのみの行は何も出力せずに次の入力を待つ
+            ExprStatus::Empty => {
+                input.clear();
+                continue;
+            }
+            // 括弧が閉じられていない場合や演算子で終わっている場合は、続きの行を読み込む
+            // 入力の終わりに達した場合は続きがないため、途中までの式を解析してエラーを報告する
+            ExprStatus::NeedsMore if !at_eof => continue,
+            ExprStatus::NeedsMore | ExprStatus::Complete | ExprStatus::Invalid => {}
         }
+        let input = std::mem::take(&mut input);
 
         match scan_tokens(&input) {
             Ok(tokens) => {
@@ -26,8 +51,8 @@ pub fn run_prompt(stdin: Stdin, mut stdout: Stdout) {
                 }
 
                 // Parserによる解析結果の追加
-                match Parser::new(tokens).parse() {
-                    Ok(expr) => writeln!(stdout, "expression: {expr:?}")
+                match Parser::new(tokens).parse_to_end() {
+                    Ok(expr) => writeln!(stdout, "{}", format_expression(&expr))
                         .expect("Error message should have been written"),
                     Err(e) => writeln!(stdout, "wrong expression: {e}")
                         .expect("Error message should have been written"),
@@ -36,5 +61,63 @@ pub fn run_prompt(stdin: Stdin, mut stdout: Stdout) {
             Err(err) => writeln!(stdout, "Error while scanning tokens: {err}")
                 .expect("Error message should have been written"),
         }
+
+        if at_eof {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::run_prompt;
+
+    #[test]
+    fn test_blank_lines() {
+        let mut output = vec![];
+        run_prompt("\n  \t\n".as_bytes(), &mut output);
+        assert_eq!(">> >> >> ", String::from_utf8_lossy(&output));
+    }
+
+    #[test]
+    fn test_continuation_lines() {
+        let mut output = vec![];
+        run_prompt("(1 +\n\n2)\n".as_bytes(), &mut output);
+
+        let output = String::from_utf8_lossy(&output);
+        assert!(output.starts_with(">> .. .. "));
+        assert!(output.contains("expression: Grouping"));
+        assert!(output.ends_with(">> "));
+    }
+
+    #[test]
+    fn test_incomplete_input_at_eof() {
+        let mut output = vec![];
+        run_prompt("(1 +\n".as_bytes(), &mut output);
+
+        let output = String::from_utf8_lossy(&output);
+        assert!(output.starts_with(">> .. "));
+        assert!(
+            output.ends_with("wrong expression: [line 2] ParserError: at end, Expect expression\n")
+        );
+
+        let mut output = vec![];
+        run_prompt("(1 + 2\n".as_bytes(), &mut output);
+        assert!(String::from_utf8_lossy(&output).ends_with(
+            "wrong expression: [line 2] ParserError: at end, expect ')' after expression\n"
+        ));
+    }
+
+    #[test]
+    fn test_comment_lines() {
+        let mut output = vec![];
+        run_prompt(
+            "// note
+/* block */
+"
+            .as_bytes(),
+            &mut output,
+        );
+        assert_eq!(">> >> >> ", String::from_utf8_lossy(&output));
     }
 }
@@ -0,0 +1,586 @@
+use std::{
+    env,
+    fs::{self, File},
+    io::{self, BufReader, IsTerminal, Write},
+    time::{Duration, Instant},
+};
+
+use serde::Serialize;
+
+use crate::{
+    expr::Expr,
+    parser::{Parser, ParserError},
+    scanner::{
+        scan_reader, scan_tokens, scan_tokens_with_warnings, ScanError, ScanOptions, ScanOutput,
+    },
+};
+
+/// コマンドラインの使い方が誤っている場合の終了コード (sysexits.h の `EX_USAGE`)
+pub const EXIT_USAGE: i32 = 64;
+/// 入力されたソースコードに誤りがある場合の終了コード (sysexits.h の `EX_DATAERR`)
+pub const EXIT_DATA_ERR: i32 = 65;
+/// 入力ファイルを読み込めなかった場合の終了コード (sysexits.h の `EX_NOINPUT`)
+pub const EXIT_NO_INPUT: i32 = 66;
+
+/// ソースコードを読み込み、読み込めなかった場合はエラーメッセージを `stderr` に出力します。
+fn read_source(path: &str, stderr: &mut impl Write) -> Option<String> {
+    match fs::read_to_string(path) {
+        Ok(source) => Some(source),
+        Err(err) => {
+            writeln!(stderr, "Error while reading {path}: {err}")
+                .expect("Error message should have been written");
+            None
+        }
+    }
+}
+
+/// 診断結果の重大度を表します。
+#[derive(PartialEq, Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "Error"),
+            Severity::Warning => write!(f, "Warning"),
+        }
+    }
+}
+
+/// 字句解析や構文解析で発生したエラーを、発生した位置とともに表します。
+///
+/// エディタなどの外部ツールから扱えるように、JSON にシリアライズすることができます。
+#[derive(PartialEq, Debug, Serialize)]
+pub struct Diagnostic {
+    /// エラーが発生した行番号
+    pub line: usize,
+    /// エラーが発生した列番号 (1始まり)
+    ///
+    /// 字句解析を経由せずに生成したトークンで発生した構文解析のエラーは、位置を持たないため `None` となります。
+    pub column: Option<usize>,
+    /// 診断結果の重大度
+    pub severity: Severity,
+    /// 利用者に表示するエラーメッセージ
+    pub message: String,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[line {}] {}: {}",
+            self.line, self.severity, self.message
+        )
+    }
+}
+
+impl Diagnostic {
+    /// 利用者に表示する形式に変換します。
+    ///
+    /// `color` が `true` の場合は、重大度を ANSI エスケープシーケンスで色付けします。
+    pub fn render(&self, color: bool) -> String {
+        if !color {
+            return self.to_string();
+        }
+
+        let code = match self.severity {
+            Severity::Error => "1;31",
+            Severity::Warning => "1;33",
+        };
+        format!(
+            "[line {}] \x1b[{code}m{}\x1b[0m: {}",
+            self.line, self.severity, self.message
+        )
+    }
+
+    /// 字句解析で発生した警告を診断結果に変換します。
+    pub fn warning(warning: ScanError) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::from(warning)
+        }
+    }
+}
+
+/// エラー出力を色付けするかどうかを判定します。
+///
+/// `--no-color` が指定された場合や環境変数 `NO_COLOR` に空でない値が設定されている場合、
+/// `stderr` が端末ではない場合は色付けしません。
+pub fn should_colorize(no_color: bool) -> bool {
+    if no_color {
+        return false;
+    }
+    if env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+        return false;
+    }
+    io::stderr().is_terminal()
+}
+
+impl From<ScanError> for Diagnostic {
+    fn from(err: ScanError) -> Self {
+        Diagnostic {
+            line: err.line,
+            column: Some(err.column),
+            severity: Severity::Error,
+            message: err.message,
+        }
+    }
+}
+
+impl From<ParserError> for Diagnostic {
+    fn from(err: ParserError) -> Self {
+        Diagnostic {
+            line: err.line,
+            column: (err.column > 0).then_some(err.column),
+            severity: Severity::Error,
+            message: err.message,
+        }
+    }
+}
+
+/// 解析に成功した場合は式と字句解析の警告を、失敗した場合はエラーと警告をまとめた診断結果を表します。
+pub type ParseResult = Result<(Expr, Vec<Diagnostic>), Vec<Diagnostic>>;
+
+/// 字句解析と構文解析を行い、発生したすべてのエラーを行番号と列番号の順に並べて返します。
+///
+/// 字句解析でエラーが発生しても、読み飛ばした後のトークン列で構文解析を続けるため、
+/// 両方のエラーをまとめて報告することができます。
+/// 成功した場合は、解析した式とともに字句解析の警告を返します。
+/// 失敗した場合は、警告もエラーと同じ配列に行番号と列番号の順に並べて返します。
+pub fn scan_and_parse_all(source: &str) -> ParseResult {
+    parse_all(scan_tokens_with_warnings(source, &ScanOptions::default()))
+}
+
+/// 字句解析の結果を構文解析し、字句解析と構文解析のすべてのエラーと警告を行番号と列番号の順に並べて返します。
+fn parse_all(scanned: ScanOutput) -> ParseResult {
+    let ScanOutput {
+        tokens,
+        errors: scan_errors,
+        warnings,
+    } = scanned;
+    let result = Parser::new(tokens).parse_to_end();
+
+    let warnings: Vec<Diagnostic> = warnings.into_iter().map(Diagnostic::warning).collect();
+    let mut diagnostics: Vec<Diagnostic> = scan_errors.into_iter().map(Diagnostic::from).collect();
+    let expr = match result {
+        Ok(expr) => Some(expr),
+        Err(err) => {
+            diagnostics.push(Diagnostic::from(err));
+            None
+        }
+    };
+
+    match expr {
+        Some(expr) if diagnostics.is_empty() => Ok((expr, warnings)),
+        _ => {
+            diagnostics.extend(warnings);
+            // 同じ位置のエラーは発生した順序を保つため、安定ソートを利用する
+            diagnostics.sort_by_key(|diagnostic| (diagnostic.line, diagnostic.column));
+            Err(diagnostics)
+        }
+    }
+}
+
+/// `format_expression` で `Debug` の形式で出力する式の入れ子の深さの上限
+///
+/// 構文解析の入れ子の上限とは異なり、`1 + 1 + ...` のような左結合の二項演算の連鎖は
+/// ループで解析されるため、この上限を超える式も構文解析には成功する
+const MAX_PRINT_DEPTH: usize = 256;
+
+/// 解析した式を `expression: ...` の形式で出力する文字列に変換します。
+///
+/// 派生した `Debug` は部分式を再帰的に出力するため、深く入れ子になった式ではスタックが溢れてしまいます。
+/// 入れ子の深さが上限を超える場合は、式の代わりに深さのみを出力します。
+pub fn format_expression(expr: &Expr) -> String {
+    match expr.depth() {
+        depth if depth > MAX_PRINT_DEPTH => {
+            format!("expression: <{depth} levels deep, too deep to print>")
+        }
+        _ => format!("expression: {expr:?}"),
+    }
+}
+
+/// 解析結果を出力し、プロセスの終了コードを返します。
+///
+/// 成功した場合は式を `stdout` に、失敗した場合はすべてのエラーを `stderr` に出力します。
+/// 警告は成功した場合も `stderr` に出力します。
+/// `color` が `true` の場合はエラーと警告を色付けします。
+fn report(result: ParseResult, color: bool, mut stdout: impl Write, mut stderr: impl Write) -> i32 {
+    match result {
+        Ok((expr, warnings)) => {
+            for warning in warnings {
+                writeln!(stderr, "{}", warning.render(color))
+                    .expect("Warning message should have been written");
+            }
+            writeln!(stdout, "{}", format_expression(&expr))
+                .expect("Expression should have been written");
+            0
+        }
+        Err(diagnostics) => {
+            for diagnostic in diagnostics {
+                writeln!(stderr, "{}", diagnostic.render(color))
+                    .expect("Error message should have been written");
+            }
+            EXIT_DATA_ERR
+        }
+    }
+}
+
+/// 指定されたファイルを解析し、結果を `stdout` に出力します。
+///
+/// 最初のエラーで中断せず、字句解析と構文解析のすべてのエラーを行番号と列番号の順に `stderr` に出力します。
+///
+/// # 戻り値
+///
+/// プロセスの終了コードを返します。成功した場合は `0` です。
+pub fn run_file(path: &str, color: bool, stdout: impl Write, mut stderr: impl Write) -> i32 {
+    let Some(source) = read_source(path, &mut stderr) else {
+        return EXIT_NO_INPUT;
+    };
+
+    report(scan_and_parse_all(&source), color, stdout, stderr)
+}
+
+/// 指定されたファイルを1行ずつ読み込みながら解析し、結果を `stdout` に出力します。
+///
+/// ファイル全体を文字列として読み込まないため、`run_file` よりもピーク時のメモリ使用量を抑えられます。
+/// 出力は `run_file` と同じです。
+///
+/// # 戻り値
+///
+/// プロセスの終了コードを返します。成功した場合は `0` です。
+pub fn run_file_streaming(
+    path: &str,
+    color: bool,
+    stdout: impl Write,
+    mut stderr: impl Write,
+) -> i32 {
+    let scanned = File::open(path)
+        .and_then(|file| scan_reader(BufReader::new(file), &ScanOptions::default()));
+    let scanned = match scanned {
+        Ok(scanned) => scanned,
+        Err(err) => {
+            writeln!(stderr, "Error while reading {path}: {err}")
+                .expect("Error message should have been written");
+            return EXIT_NO_INPUT;
+        }
+    };
+
+    report(parse_all(scanned), color, stdout, stderr)
+}
+
+/// 指定されたファイルの構文だけを検査し、エラーがあれば行番号と列番号の順に `stderr` に出力します。
+///
+/// CI などで利用することを想定しており、成功した場合は警告のみを出力します。
+///
+/// # 戻り値
+///
+/// プロセスの終了コードを返します。成功した場合は `0`、エラーがあった場合は `EXIT_DATA_ERR` です。
+pub fn check_file(path: &str, color: bool, mut stderr: impl Write) -> i32 {
+    let Some(source) = read_source(path, &mut stderr) else {
+        return EXIT_NO_INPUT;
+    };
+
+    // 解析した式は出力しない
+    report(scan_and_parse_all(&source), color, io::sink(), stderr)
+}
+
+/// 指定されたファイルを解析し、発生したすべてのエラーを JSON の配列として `stdout` に出力します。
+///
+/// エディタのプラグインなどから利用することを想定しており、各要素は `line`, `column`,
+/// `severity`, `message` を持ちます。エラーも警告もない場合は空の配列を出力します。
+///
+/// # 戻り値
+///
+/// ソースコードにエラーがあっても `0` を返します。ファイルを読み込めなかった場合のみ `EXIT_NO_INPUT` を返します。
+pub fn diagnostics_json(path: &str, mut stdout: impl Write, mut stderr: impl Write) -> i32 {
+    let Some(source) = read_source(path, &mut stderr) else {
+        return EXIT_NO_INPUT;
+    };
+
+    let diagnostics = match scan_and_parse_all(&source) {
+        Ok((_, warnings)) => warnings,
+        Err(diagnostics) => diagnostics,
+    };
+    let json = serde_json::to_string(&diagnostics).expect("diagnostics should be serializable");
+    writeln!(stdout, "{json}").expect("JSON should have been written");
+    0
+}
+
+/// 指定されたファイルを字句解析し、トークンの配列を JSON として出力します。
+///
+/// 外部の解析ツールから `jq` などで扱えるように、JSON は `stdout` にのみ出力し、
+/// エラーメッセージは `stderr` に出力します。
+///
+/// # 戻り値
+///
+/// プロセスの終了コードを返します。成功した場合は `0` です。
+pub fn dump_tokens_json(path: &str, mut stdout: impl Write, mut stderr: impl Write) -> i32 {
+    let Some(source) = read_source(path, &mut stderr) else {
+        return EXIT_NO_INPUT;
+    };
+
+    match scan_tokens(&source) {
+        Ok(tokens) => {
+            let json = serde_json::to_string(&tokens).expect("tokens should be serializable");
+            writeln!(stdout, "{json}").expect("JSON should have been written");
+            0
+        }
+        Err(err) => {
+            writeln!(stderr, "Error while scanning tokens: {err}")
+                .expect("Error message should have been written");
+            EXIT_DATA_ERR
+        }
+    }
+}
+
+/// 各フェーズの処理に要した時間を表す構造体です
+#[derive(Debug, Default)]
+pub struct Timings {
+    /// 字句解析に要した時間
+    pub scan: Duration,
+    /// 構文解析に要した時間
+    pub parse: Duration,
+}
+
+impl Timings {
+    /// すべてのフェーズの合計時間を返します
+    pub fn total(&self) -> Duration {
+        self.scan + self.parse
+    }
+}
+
+/// 字句解析と構文解析を実行し、それぞれのフェーズに要した時間を計測します。
+///
+/// 解析の結果は `scan_and_parse_all` と同じであり、字句解析でエラーが発生しても構文解析まで行います。
+pub fn scan_and_parse_timed(source: &str) -> (ParseResult, Timings) {
+    let mut timings = Timings::default();
+
+    let start = Instant::now();
+    let scanned = scan_tokens_with_warnings(source, &ScanOptions::default());
+    timings.scan = start.elapsed();
+
+    let start = Instant::now();
+    let result = parse_all(scanned);
+    timings.parse = start.elapsed();
+
+    (result, timings)
+}
+
+/// 指定されたファイルを解析して結果を `stdout` に出力し、各フェーズの所要時間を `stderr` に出力します。
+///
+/// 所要時間の後に出力する結果は `run_file` と同じです。
+///
+/// # 戻り値
+///
+/// プロセスの終了コードを返します。成功した場合は `0` です。
+pub fn run_file_timed(path: &str, color: bool, stdout: impl Write, mut stderr: impl Write) -> i32 {
+    let Some(source) = read_source(path, &mut stderr) else {
+        return EXIT_NO_INPUT;
+    };
+
+    let (result, timings) = scan_and_parse_timed(&source);
+    writeln!(stderr, "scan:  {:?}", timings.scan).expect("Timings should have been written");
+    writeln!(stderr, "parse: {:?}", timings.parse).expect("Timings should have been written");
+    writeln!(stderr, "total: {:?}", timings.total()).expect("Timings should have been written");
+
+    report(result, color, stdout, stderr)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{
+        format_expression, run_file, run_file_streaming, scan_and_parse_all, scan_and_parse_timed,
+        should_colorize, Diagnostic, Severity,
+    };
+
+    #[test]
+    fn test_scan_and_parse_timed() {
+        let (result, timings) = scan_and_parse_timed("1 + 2 * 3");
+
+        result.expect("Failed to scan and parse input string");
+        assert_eq!(timings.scan + timings.parse, timings.total());
+
+        let (result, _) = scan_and_parse_timed("(1 + @");
+
+        let diagnostics = result.expect_err("Unexpectedly Success to scan input string");
+        assert_eq!(
+            scan_and_parse_all("(1 + @").expect_err("Unexpectedly Success to scan input string"),
+            diagnostics
+        );
+    }
+
+    #[test]
+    fn test_scan_and_parse_all() {
+        let diagnostics = scan_and_parse_all("(1 +\n2 # 3\n@")
+            .expect_err("Unexpectedly Success to scan and parse input string");
+
+        assert_eq!(
+            vec![
+                Diagnostic {
+                    line: 2,
+                    column: Some(3),
+                    severity: Severity::Error,
+                    message: "invalid token: #".to_string(),
+                },
+                Diagnostic {
+                    line: 2,
+                    column: Some(5),
+                    severity: Severity::Error,
+                    message: "at '3', expect ')' after expression".to_string(),
+                },
+                Diagnostic {
+                    line: 3,
+                    column: Some(1),
+                    severity: Severity::Error,
+                    message: "invalid token: @".to_string(),
+                },
+            ],
+            diagnostics
+        );
+
+        let diagnostics = scan_and_parse_all("(1 2 @")
+            .expect_err("Unexpectedly Success to scan and parse input string");
+        assert_eq!(
+            vec![Some(4), Some(6)],
+            diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.column)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_precision_warning() {
+        let warning = Diagnostic {
+            line: 2,
+            column: Some(5),
+            severity: Severity::Warning,
+            message: "integer literal 9007199254740993 cannot be represented exactly and becomes 9007199254740992".to_string(),
+        };
+
+        let (_, warnings) = scan_and_parse_all("1 +\n    9007199254740993")
+            .expect("Failed to scan and parse input string");
+        assert_eq!(vec![warning], warnings);
+        assert_eq!(
+            "[line 2] \x1b[1;33mWarning\x1b[0m: integer literal 9007199254740993 cannot be represented exactly and becomes 9007199254740992",
+            warnings[0].render(true)
+        );
+
+        let diagnostics = scan_and_parse_all("9007199254740993 @")
+            .expect_err("Unexpectedly Success to scan input string");
+        assert_eq!(
+            vec![Severity::Warning, Severity::Error],
+            diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.severity)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_format_deeply_nested_expression() {
+        let source = vec!["1"; 100_000].join("+");
+        let (expr, _) = scan_and_parse_all(&source).expect("Failed to scan and parse input string");
+        assert_eq!(
+            "expression: <100000 levels deep, too deep to print>",
+            format_expression(&expr)
+        );
+
+        let (expr, _) = scan_and_parse_all("1").expect("Failed to scan and parse input string");
+        assert_eq!(
+            "expression: Literal { value: Number(1.0), line: 1 }",
+            format_expression(&expr)
+        );
+    }
+
+    #[test]
+    fn test_trailing_tokens() {
+        let diagnostics =
+            scan_and_parse_all("1 2").expect_err("Unexpectedly Success to parse input string");
+        assert_eq!(
+            vec![Diagnostic {
+                line: 1,
+                column: Some(3),
+                severity: Severity::Error,
+                message: "at '2', Expect end of expression".to_string(),
+            }],
+            diagnostics
+        );
+
+        let diagnostics = scan_and_parse_all("1 + 2 3 @@ )))")
+            .expect_err("Unexpectedly Success to parse input string");
+        assert_eq!(
+            vec![
+                "at '3', Expect end of expression",
+                "invalid token: @",
+                "invalid token: @",
+            ],
+            diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.message.as_str())
+                .collect::<Vec<_>>()
+        );
+
+        let (result, _) = scan_and_parse_timed("1 2");
+        result.expect_err("Unexpectedly Success to parse input string");
+    }
+
+    #[test]
+    fn test_run_file_streaming() {
+        let mut source = String::from("[\n");
+        for i in 0..10_000 {
+            source.push_str(&format!("  \"line {i}\nstring\", {i},\n"));
+        }
+        source.push_str("  nil]\n");
+        // `CARGO_TARGET_TMPDIR` は結合テストでしか設定されないため、プロセスごとに異なる名前を付けて衝突を避ける
+        let path =
+            std::env::temp_dir().join(format!("run_file_streaming_{}.lox", std::process::id()));
+        fs::write(&path, source).expect("Failed to write source file");
+        let path = path.to_str().unwrap();
+
+        let (mut expected, mut actual) = (vec![], vec![]);
+        assert_eq!(0, run_file(path, false, &mut expected, std::io::sink()));
+        assert_eq!(
+            0,
+            run_file_streaming(path, false, &mut actual, std::io::sink())
+        );
+        assert_eq!(expected, actual);
+
+        fs::write(path, "(1 +\n2 @ 3\n").expect("Failed to write source file");
+        let (mut expected, mut actual) = (vec![], vec![]);
+        assert_eq!(65, run_file(path, false, std::io::sink(), &mut expected));
+        assert_eq!(
+            65,
+            run_file_streaming(path, false, std::io::sink(), &mut actual)
+        );
+        assert_eq!(expected, actual);
+
+        fs::remove_file(path).expect("Failed to remove source file");
+    }
+
+    #[test]
+    fn test_render_diagnostic() {
+        let diagnostic = Diagnostic {
+            line: 3,
+            column: Some(1),
+            severity: Severity::Error,
+            message: "invalid token: @".to_string(),
+        };
+
+        let plain = diagnostic.render(false);
+        assert_eq!("[line 3] Error: invalid token: @", plain);
+        assert!(!plain.contains('\x1b'));
+
+        let colored = diagnostic.render(true);
+        assert_eq!("[line 3] \x1b[1;31mError\x1b[0m: invalid token: @", colored);
+
+        assert!(!should_colorize(true));
+    }
+}
@@ -1,51 +1,656 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    fs,
+    io::{self, BufRead},
+    path::PathBuf,
+    rc::Rc,
+};
+
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
 use crate::token::{match_keywords, Literal, Token, TokenType};
 
+/// `Scanner`が読み進める入力文字列を抽象化するトレイト
+///
+/// 位置 `position` の単位は実装ごとに異なり、`Vec<char>` では文字単位、`&str` ではバイト単位となる
+trait Source {
+    /// 入力文字列の終端の位置を返す
+    fn end(&self) -> usize;
+    /// `position` にある文字を返す。終端に達している場合は `None` を返す
+    fn char_at(&self, position: usize) -> Option<char>;
+    /// 文字 `c` が占める位置の幅を返す
+    fn width(c: char) -> usize;
+    /// `start` から `end` までの範囲の文字列を返す
+    fn slice(&self, start: usize, end: usize) -> Cow<'_, str>;
+    /// `start` から `end` までの範囲の文字列を、`Token` の字句と同じ `Vec<char>` として返す
+    fn chars(&self, start: usize, end: usize) -> Vec<char>;
+}
+
+/// マルチバイトのUTF-8文字も安全に取り扱えるように、入力文字列を char 型の配列として保持する
+impl Source for Vec<char> {
+    fn end(&self) -> usize {
+        self.len()
+    }
+
+    fn char_at(&self, position: usize) -> Option<char> {
+        self.get(position).copied()
+    }
+
+    fn width(_: char) -> usize {
+        1
+    }
+
+    fn slice(&self, start: usize, end: usize) -> Cow<'_, str> {
+        Cow::Owned(self[start..end].iter().collect())
+    }
+
+    fn chars(&self, start: usize, end: usize) -> Vec<char> {
+        self[start..end].to_vec()
+    }
+}
+
+/// 入力文字列を複製せずに借用し、UTF-8 のバイト位置で読み進める
+///
+/// `Vec<char>` は1文字あたり4バイトを確保するため、大きなファイルでは入力の4倍程度のメモリを追加で消費する。
+/// こちらは入力文字列をそのまま参照するため、その確保が不要になる。
+/// ただし `Token` の字句は `Vec<char>` として所有するため、トークン自体のメモリ使用量は変わらない。
+impl Source for &str {
+    fn end(&self) -> usize {
+        self.len()
+    }
+
+    fn char_at(&self, position: usize) -> Option<char> {
+        self.get(position..)?.chars().next()
+    }
+
+    fn width(c: char) -> usize {
+        c.len_utf8()
+    }
+
+    fn slice(&self, start: usize, end: usize) -> Cow<'_, str> {
+        Cow::Borrowed(&self[start..end])
+    }
+
+    fn chars(&self, start: usize, end: usize) -> Vec<char> {
+        self[start..end].chars().collect()
+    }
+}
+
+/// 字句解析の挙動を切り替えるためのオプションです。
+///
+/// `Default` はすべてのオプションが無効かつタブ幅が `1` の状態であり、`scan_tokens` と同じ挙動になります。
+///
+/// # 例
+///
+/// ```
+/// let options = ScanOptions {
+///     case_insensitive_keywords: true,
+///     ..Default::default()
+/// };
+/// let tokens = scan_tokens_with("IF", &options);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    /// `IF` や `While` のように大文字を含む場合でもキーワードとして扱う
+    ///
+    /// キーワードに該当しない識別子は元の大文字・小文字を保持する
+    pub case_insensitive_keywords: bool,
+    /// 改行を `;` の代わりに文を終端するトークン `TokenType::Newline` として出力する
+    ///
+    /// 丸括弧や角括弧の内側の改行は式の継続とみなして出力しない。
+    /// 空行やファイル先頭の改行のように、直前に終端すべき文がない場合も出力しない。
+    pub newline_terminators: bool,
+    /// 列番号を数える際のタブ文字の幅
+    ///
+    /// タブ文字の次の文字は、`tab_width` の倍数に1を足した列まで進める。
+    /// エディタの表示と列番号を揃えるためのものであり、トークンの字句には影響しない。
+    pub tab_width: usize,
+    /// 空白として現れたタブ文字をエラーとする
+    ///
+    /// 文字列リテラルやコメントの内側のタブ文字は対象外とする
+    pub forbid_tabs: bool,
+    /// 識別子に ASCII 以外の文字を許可し、NFC 正規化した名前で識別子のリテラル値を生成する
+    ///
+    /// 合成済み文字と結合文字列のように見た目が同じ識別子を、同じ名前として扱うためのもの。
+    /// キーワードの判定は正規化した後に行い、トークンの字句にはソースコード上の表記をそのまま残す。
+    pub normalize_identifiers: bool,
+    /// 識別子・数値・文字列リテラルの最大文字数。`None` の場合は制限しない
+    ///
+    /// 文字列リテラルは前後のダブルクォートを含めず、エスケープシーケンスを解釈した後の文字数を数える。
+    /// 信頼できない入力を解析する際に、巨大な単一のトークンによる大きなメモリ確保を防ぐためのもの。
+    /// 上限を超えた時点で文字列の値の構築を打ち切り、トークンの終わりまで読み飛ばしてエラーとする。
+    pub max_token_length: Option<usize>,
+    /// エラーが発生した場合に、その行の残りを読み飛ばして次の行から解析を再開する
+    ///
+    /// 既定では不正な1文字だけを読み飛ばすため、大きく崩れた行では後続のエラーが連鎖しやすい。
+    /// 改行自体は読み飛ばさないため、行番号や `newline_terminators` の扱いは変わらない。
+    pub resync_on_newline: bool,
+    /// 組み込みのキーワード表よりも優先して参照するキーワード表
+    ///
+    /// `let` を `TokenType::Var` とするようにキーワードを追加したり、別の型に置き換えたりできる。
+    /// `TokenType::Identifier` を指定した名前は、組み込みのキーワードであっても識別子として扱う。
+    /// `case_insensitive_keywords` を有効にする場合は、小文字の名前で登録する。
+    pub keywords: HashMap<String, TokenType>,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        ScanOptions {
+            case_insensitive_keywords: false,
+            newline_terminators: false,
+            tab_width: 1,
+            forbid_tabs: false,
+            normalize_identifiers: false,
+            max_token_length: None,
+            resync_on_newline: false,
+            keywords: HashMap::new(),
+        }
+    }
+}
+
 /// `Scanner`は、入力された文字列をトークンの配列に解析するための構造体
-struct Scanner {
+struct Scanner<'a, S: Source> {
     /// 入力文字列を保持する
-    /// マルチバイトのUTF-8文字も安全に取り扱えるように char 型として保持する
-    pub source: Vec<char>,
+    pub source: S,
+    /// 字句解析の挙動を切り替えるオプション
+    pub options: &'a ScanOptions,
     /// 字句解析した結果のトークンを保持する
     pub tokens: Vec<Token>,
+    /// 字句解析中に発生したエラーを保持する
+    pub errors: Vec<ScanError>,
+    /// 字句解析中に発生した警告を保持する
+    pub warnings: Vec<ScanError>,
     /// スキャン中のトークンの最初の文字の位置を指す
     pub start: usize,
     /// スキャン中に注目している文字を指す
     pub current: usize,
     /// `current`が入力文字列の何行目に当たるのかを追跡管理する
     pub line: usize,
+    /// `current`が行の何文字目に当たるのかを追跡管理する (1始まり)
+    pub column: usize,
+    /// スキャン中のトークンの最初の文字が何行目に当たるのかを保持する
+    pub start_line: usize,
+    /// スキャン中のトークンの最初の文字が行の何文字目に当たるのかを保持する
+    pub start_column: usize,
+    /// `current`が入力文字列の先頭から何バイト目に当たるのかを追跡管理する
+    ///
+    /// `current` の単位は `Source` の実装ごとに異なるため、トークンのバイト位置はこちらから求める
+    pub offset: usize,
+    /// スキャン中のトークンの最初の文字が入力文字列の先頭から何バイト目に当たるのかを保持する
+    pub start_offset: usize,
+    /// 閉じられていない丸括弧と角括弧の数を保持する
+    pub nesting: usize,
+    /// 文字列やブロックコメントが閉じられないまま入力の終わりに達したかどうかを保持する
+    pub unterminated: bool,
+    /// 同じ名前の識別子でリテラル値の文字列を共有するために、これまでに現れた識別子の名前を保持する
+    pub identifiers: HashSet<Rc<str>>,
+    /// 閉じられないまま入力の終わりに達した文字列やブロックコメントの途中の状態を保持する
+    pub pending: Option<Pending>,
+    /// `resume` で再開した文字列の、前の入力までの字句を保持する
+    pub carried: String,
+}
+
+/// 閉じられないまま入力の終わりに達したトークンの、解析途中の状態
+///
+/// `scan_reader` で1行ずつ字句解析する際に、次の行からトークンの続きを解析するために利用する
+#[derive(Debug)]
+enum Pending {
+    /// 文字列リテラルの途中
+    String {
+        /// 開始のダブルクォートからのソースコード上の表記
+        lexeme: String,
+        /// エスケープシーケンスを解釈した後の文字列
+        literal: String,
+        /// `literal` の文字数
+        length: usize,
+        /// それまでに見つかった不正なエスケープシーケンスなどのエラー
+        error: Option<String>,
+    },
+    /// ブロックコメントの途中
+    BlockComment,
+}
+
+/// 字句解析エラーを表すカスタムエラー型です。
+///
+/// 複数のエラーを行番号順に並べて報告できるように、エラーが発生した行番号を保持します。
+#[derive(PartialEq, Debug, Clone)]
+pub struct ScanError {
+    /// エラーが発生したトークンの開始位置の行番号
+    ///
+    /// 閉じられていない文字列では、開始のダブルクォートがある行を指します。
+    pub line: usize,
+    /// エラーが発生したトークンの開始位置が行の何文字目に当たるか (1始まり)
+    pub column: usize,
+    /// エラーメッセージ
+    pub message: String,
+}
+
+impl std::error::Error for ScanError {}
+
+impl std::fmt::Display for ScanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[line {}] ScanError: {}", self.line, self.message)
+    }
+}
+
+pub fn scan_tokens(input: &str) -> Result<Vec<Token>, ScanError> {
+    scan_tokens_with(input, &ScanOptions::default())
+}
+
+/// オプションを指定して字句解析します。
+///
+/// エラーが発生した場合は、最初に発生したエラーを返します。
+pub fn scan_tokens_with(input: &str, options: &ScanOptions) -> Result<Vec<Token>, ScanError> {
+    let (tokens, errors) = scan_tokens_collecting(input, options);
+    match errors.into_iter().next() {
+        Some(error) => Err(error),
+        None => Ok(tokens),
+    }
+}
+
+/// 最初のエラーで中断せずに最後まで字句解析し、トークンと発生したすべてのエラーを返します。
+///
+/// 不正な文字はその1文字を読み飛ばして解析を続けるため、エラーがあってもトークン列を構文解析に渡すことができます。
+pub fn scan_tokens_collecting(input: &str, options: &ScanOptions) -> (Vec<Token>, Vec<ScanError>) {
+    let output = scan_tokens_with_warnings(input, options);
+    (output.tokens, output.errors)
+}
+
+/// 字句解析の結果を、発生したエラーと警告とともに保持します。
+#[derive(PartialEq, Debug)]
+pub struct ScanOutput {
+    /// 字句解析したトークン
+    pub tokens: Vec<Token>,
+    /// 発生したすべてのエラー
+    pub errors: Vec<ScanError>,
+    /// 解析は続けられるものの、意図しない結果となる可能性がある箇所
+    pub warnings: Vec<ScanError>,
+}
+
+/// `scan_tokens_collecting` と同様に最後まで字句解析し、エラーに加えて警告も返します。
+///
+/// 現在報告する警告は、`f64` で正確に表せないほど大きな整数リテラルのみです。
+pub fn scan_tokens_with_warnings(input: &str, options: &ScanOptions) -> ScanOutput {
+    let mut scanner = Scanner::new(input.chars().collect::<Vec<char>>(), options);
+    scanner.scan_tokens();
+    ScanOutput {
+        tokens: scanner.tokens,
+        errors: scanner.errors,
+        warnings: scanner.warnings,
+    }
+}
+
+/// `tokenize_expr` による判定結果を表します。
+#[derive(PartialEq, Debug)]
+pub enum ExprStatus {
+    /// 括弧が閉じられており、演算子で終わっていない
+    Complete,
+    /// 括弧や文字列が閉じられていない、または演算子で終わっているため、続きの入力が必要
+    NeedsMore,
+    /// 不正な文字や対応しない閉じ括弧を含むため、続きを入力しても完全な式にならない
+    Invalid,
+    /// 空白やコメントのみで、式を含まない
+    Empty,
+}
+
+/// 1つの式を字句解析し、構文解析器を使わずに式が完結しているかどうかを判定します。
+///
+/// REPL で継続行のプロンプトを表示するかどうかを決めるための簡易的な判定であるため、
+/// `Complete` であっても構文解析に成功するとは限りません。
+pub fn tokenize_expr(input: &str) -> ExprStatus {
+    use TokenType::*;
+
+    let options = ScanOptions::default();
+    let mut scanner = Scanner::new(input, &options);
+    scanner.scan_tokens();
+
+    // 閉じられていない文字列やコメントのエラーは、続きを入力すれば解消する
+    if scanner.errors.len() > usize::from(scanner.unterminated) {
+        return ExprStatus::Invalid;
+    }
+    if scanner.unterminated {
+        return ExprStatus::NeedsMore;
+    }
+
+    let mut brackets = vec![];
+    for token in &scanner.tokens {
+        let open = match token.ty {
+            LParan | LBracket | LBrace => {
+                brackets.push(&token.ty);
+                continue;
+            }
+            RParan => LParan,
+            RBracket => LBracket,
+            RBrace => LBrace,
+            _ => continue,
+        };
+        if brackets.pop() != Some(&open) {
+            return ExprStatus::Invalid;
+        }
+    }
+    if !brackets.is_empty() {
+        return ExprStatus::NeedsMore;
+    }
+
+    // 末尾の `Eof` を除いた最後のトークンが演算子であれば、右辺の入力が続く
+    match scanner.tokens.iter().rev().nth(1) {
+        None => ExprStatus::Empty,
+        Some(token)
+            if matches!(
+                token.ty,
+                Plus | Minus
+                    | Star
+                    | StarStar
+                    | Slash
+                    | Bang
+                    | BangEqual
+                    | Equal
+                    | EqualEqual
+                    | Greater
+                    | GreaterEqual
+                    | Less
+                    | LessEqual
+                    | Comma
+                    | Colon
+                    | Dot
+                    | And
+                    | Or
+            ) =>
+        {
+            ExprStatus::NeedsMore
+        }
+        Some(_) => ExprStatus::Complete,
+    }
+}
+
+/// 入力文字列を `Vec<char>` に複製せず、借用したまま字句解析します。
+///
+/// 生成されるトークンは `scan_tokens` と同じですが、入力文字列全体を複製しない分だけ
+/// 大きなファイルを解析する際のメモリ使用量を抑えることができます。
+pub fn scan_tokens_borrowed(input: &str) -> Result<Vec<Token>, ScanError> {
+    let options = ScanOptions::default();
+    let mut scanner = Scanner::new(input, &options);
+    scanner.scan_tokens();
+    match scanner.errors.into_iter().next() {
+        Some(error) => Err(error),
+        None => Ok(scanner.tokens),
+    }
+}
+
+/// `reader` から1行ずつ読み込みながら字句解析し、トークンと発生したすべてのエラーを返します。
+///
+/// 入力全体を一度に保持しないため、大きなファイルでもピーク時のメモリ使用量を抑えることができます。
+/// 行末までに閉じられなかった文字列やブロックコメントは、途中の状態を引き継いで次の行から解析を再開するため、
+/// 結果は `scan_tokens_with_warnings` で入力全体を解析した場合と同じになります。
+/// 保持し続けるのは閉じられていない文字列の字句のみであり、コメントの内容は保持しません。
+pub fn scan_reader(mut reader: impl BufRead, options: &ScanOptions) -> io::Result<ScanOutput> {
+    let mut tokens = vec![];
+    let mut errors = vec![];
+    let mut warnings = vec![];
+    // 読み込んだ1行を保持する
+    let mut chunk = String::new();
+    let mut line = 1;
+    let mut column = 1;
+    let mut offset = 0;
+    let mut nesting = 0;
+    let mut identifiers = HashSet::new();
+    // 前の行で閉じられなかった文字列やブロックコメントと、その開始位置 (行番号, 列番号, バイト位置)
+    let mut pending = None;
+    let mut start = (1, 1, 0);
+
+    loop {
+        chunk.clear();
+        let at_eof = reader.read_line(&mut chunk)? == 0;
+
+        let mut scanner = Scanner::new(chunk.as_str(), options);
+        // 改行を出力するかどうかは直前のトークンで決まるため、これまでのトークンを引き継ぐ
+        scanner.tokens = std::mem::take(&mut tokens);
+        scanner.errors = std::mem::take(&mut errors);
+        scanner.warnings = std::mem::take(&mut warnings);
+        scanner.line = line;
+        scanner.column = column;
+        scanner.offset = offset;
+        scanner.nesting = nesting;
+        scanner.identifiers = std::mem::take(&mut identifiers);
+        if let Some(pending) = pending.take() {
+            (
+                scanner.start_line,
+                scanner.start_column,
+                scanner.start_offset,
+            ) = start;
+            scanner.resume(pending);
+        } else if line == 1 {
+            scanner.skip_bom();
+            scanner.skip_shebang();
+        }
+        scanner.scan_to_end();
+
+        // 文字列やブロックコメントが閉じられていない場合は、エラーとせずに途中の状態を次の行へ引き継ぐ
+        if scanner.unterminated && !at_eof {
+            scanner.errors.pop();
+            pending = scanner.pending.take();
+            start = (
+                scanner.start_line,
+                scanner.start_column,
+                scanner.start_offset,
+            );
+        }
+
+        line = scanner.line;
+        column = scanner.column;
+        offset = scanner.offset;
+        nesting = scanner.nesting;
+        identifiers = scanner.identifiers;
+        tokens = scanner.tokens;
+        errors = scanner.errors;
+        warnings = scanner.warnings;
+
+        if at_eof {
+            break;
+        }
+    }
+
+    tokens.push(Token {
+        ty: TokenType::Eof,
+        lexeme: vec![],
+        literal: None,
+        line,
+        column,
+        offset,
+    });
+    Ok(ScanOutput {
+        tokens,
+        errors,
+        warnings,
+    })
 }
 
-pub fn scan_tokens(input: &str) -> Result<Vec<Token>, String> {
-    let mut scanner = Scanner::new(input);
-    scanner.scan_tokens()?;
-    Ok(scanner.tokens)
+/// 複数のソースをまとめて字句解析したトークンです。
+#[derive(PartialEq, Debug)]
+pub struct FileToken {
+    /// トークンが属するソースの番号 (`scan_files` に渡したパスの添字)
+    pub file: usize,
+    /// トークン本体。行番号や列番号は `file` のソース内での位置を表します。
+    pub token: Token,
 }
 
-impl Scanner {
-    fn new(input: &str) -> Self {
+/// どのソースで発生したかを保持する字句解析エラーです。
+#[derive(PartialEq, Debug, Clone)]
+pub struct FileScanError {
+    /// エラーが発生したソースの番号 (`scan_files` に渡したパスの添字)
+    pub file: usize,
+    /// 発生したエラー
+    pub error: ScanError,
+}
+
+impl std::error::Error for FileScanError {}
+
+impl std::fmt::Display for FileScanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[file {}] {}", self.file, self.error)
+    }
+}
+
+/// 複数のファイルを順に字句解析し、1つのトークン列にまとめて返します。
+///
+/// ファイルの読み込みに失敗した場合は `io::Error` を返します。
+/// 字句解析の結果は `scan_sources` と同じです。
+pub fn scan_files(paths: &[PathBuf]) -> io::Result<Result<Vec<FileToken>, Vec<FileScanError>>> {
+    let sources = paths
+        .iter()
+        .map(fs::read_to_string)
+        .collect::<io::Result<Vec<String>>>()?;
+    let sources: Vec<&str> = sources.iter().map(String::as_str).collect();
+    Ok(scan_sources(&sources))
+}
+
+/// 複数のソースを順に字句解析し、各トークンに属するソースの番号を付けて1つのトークン列にまとめます。
+///
+/// 途中のソースの `Eof` は取り除き、最後のソースの `Eof` だけを残します。
+/// いずれかのソースでエラーが発生した場合は、すべてのソースで発生したエラーを返します。
+pub fn scan_sources(sources: &[&str]) -> Result<Vec<FileToken>, Vec<FileScanError>> {
+    let mut tokens = vec![];
+    let mut errors = vec![];
+
+    for (file, source) in sources.iter().enumerate() {
+        let (scanned, scan_errors) = scan_tokens_collecting(source, &ScanOptions::default());
+        let is_last = file + 1 == sources.len();
+        tokens.extend(
+            scanned
+                .into_iter()
+                .filter(|token| is_last || token.ty != TokenType::Eof)
+                .map(|token| FileToken { file, token }),
+        );
+        errors.extend(
+            scan_errors
+                .into_iter()
+                .map(|error| FileScanError { file, error }),
+        );
+    }
+
+    if errors.is_empty() {
+        Ok(tokens)
+    } else {
+        Err(errors)
+    }
+}
+
+impl<'a, S: Source> Scanner<'a, S> {
+    fn new(source: S, options: &'a ScanOptions) -> Self {
         Scanner {
-            source: input.chars().collect(),
+            source,
+            options,
             tokens: vec![],
+            errors: vec![],
+            warnings: vec![],
             start: 0,
             current: 0,
             line: 1,
+            column: 1,
+            start_line: 1,
+            start_column: 1,
+            offset: 0,
+            start_offset: 0,
+            nesting: 0,
+            unterminated: false,
+            identifiers: HashSet::new(),
+            pending: None,
+            carried: String::new(),
         }
     }
 
-    fn scan_tokens(&mut self) -> Result<(), String> {
-        while !self.is_at_end() {
-            self.start = self.current;
-            self.scan_token()?;
-        }
+    fn scan_tokens(&mut self) {
+        self.skip_bom();
+        self.skip_shebang();
+        self.scan_to_end();
 
         self.tokens.push(Token {
             ty: TokenType::Eof,
             lexeme: vec![],
             literal: None,
             line: self.line,
+            column: self.column,
+            offset: self.offset,
         });
+    }
 
-        Ok(())
+    /// 入力の終わりまでトークンを読み進める。終端トークン `Eof` は追加しない
+    fn scan_to_end(&mut self) {
+        while !self.is_at_end() {
+            self.start = self.current;
+            self.start_line = self.line;
+            self.start_column = self.column;
+            self.start_offset = self.offset;
+            if let Err(message) = self.scan_token() {
+                self.report(message);
+            }
+        }
+    }
+
+    /// スキャン中のトークンで発生したエラーを記録する
+    ///
+    /// 複数行にまたがる文字列のエラーでも開始位置を指せるように、トークンの開始位置を報告する
+    fn report(&mut self, message: String) {
+        self.errors.push(ScanError {
+            line: self.start_line,
+            column: self.start_column,
+            message,
+        });
+        if self.options.resync_on_newline {
+            self.skip_line();
+        }
+    }
+
+    /// 前の入力で閉じられなかった文字列やブロックコメントの続きから解析を再開する
+    ///
+    /// トークンの開始位置 `start_line` などは、呼び出す前に前の入力での値に戻しておく
+    fn resume(&mut self, pending: Pending) {
+        let result = match pending {
+            Pending::String {
+                lexeme,
+                literal,
+                length,
+                error,
+            } => {
+                self.carried = lexeme;
+                self.string_rest(literal, length, error)
+            }
+            Pending::BlockComment => self.block_comment(),
+        };
+        self.carried.clear();
+
+        if let Err(message) = result {
+            self.report(message);
+        }
+    }
+
+    /// 現在の行の残りを、改行の手前まで読み飛ばす
+    fn skip_line(&mut self) {
+        while self.peek() != '\n' && !self.is_at_end() {
+            self.advance();
+        }
+    }
+
+    /// UTF-8 の BOM 付きで保存されたファイルの先頭にある U+FEFF を読み飛ばす
+    ///
+    /// BOM は列番号に数えない。先頭以外の U+FEFF はこれまで通り不正な文字として扱う
+    fn skip_bom(&mut self) {
+        if self.current == 0 && self.peek() == '\u{feff}' {
+            self.current += S::width('\u{feff}');
+            self.offset += '\u{feff}'.len_utf8();
+        }
+    }
+
+    /// 実行可能なスクリプトにするための先頭行の `#!...` を、改行まで読み飛ばす
+    ///
+    /// 先頭行以外の `#` はこれまで通り不正な文字として扱う
+    fn skip_shebang(&mut self) {
+        if self.peek() != '#' || self.peek_next() != '!' {
+            return;
+        }
+
+        while !self.is_at_end() && self.advance() != '\n' {}
     }
 
     fn scan_token(&mut self) -> Result<(), String> {
@@ -53,23 +658,49 @@ impl Scanner {
         match c {
             '{' => self.add_token(TokenType::LBrace),
             '}' => self.add_token(TokenType::RBrace),
-            '(' => self.add_token(TokenType::LParan),
-            ')' => self.add_token(TokenType::RParan),
+            '(' => {
+                self.nesting += 1;
+                self.add_token(TokenType::LParan)
+            }
+            ')' => {
+                self.nesting = self.nesting.saturating_sub(1);
+                self.add_token(TokenType::RParan)
+            }
+            '[' => {
+                self.nesting += 1;
+                self.add_token(TokenType::LBracket)
+            }
+            ']' => {
+                self.nesting = self.nesting.saturating_sub(1);
+                self.add_token(TokenType::RBracket)
+            }
             ',' => self.add_token(TokenType::Comma),
+            ':' => self.add_token(TokenType::Colon),
             '.' => self.add_token(TokenType::Dot),
             '-' => self.add_token(TokenType::Minus),
             '+' => self.add_token(TokenType::Plus),
             ';' => self.add_token(TokenType::SemiColon),
+            // `/` から始まるトークンは、2文字目で判別できるものから順に判定する
             '/' => {
                 if self.matches('/') {
                     while self.peek() != '\n' && !self.is_at_end() {
                         self.advance();
                     }
+                } else if self.matches('*') {
+                    self.block_comment()?;
+                } else if self.matches('=') {
+                    self.add_token(TokenType::SlashEqual)
                 } else {
                     self.add_token(TokenType::Slash)
                 }
             }
-            '*' => self.add_token(TokenType::Star),
+            '*' => {
+                if self.matches('*') {
+                    self.add_token(TokenType::StarStar)
+                } else {
+                    self.add_token(TokenType::Star)
+                }
+            }
             '!' => {
                 if self.matches('=') {
                     self.add_token(TokenType::BangEqual)
@@ -98,16 +729,18 @@ impl Scanner {
                     self.add_token(TokenType::Less)
                 }
             }
+            // C 系の言語に慣れた利用者向けに、論理演算子のキーワードを案内する
+            '&' if self.matches('&') => return Err(String::from("use 'and' instead of '&&'")),
+            '|' if self.matches('|') => return Err(String::from("use 'or' instead of '||'")),
+            '\t' if self.options.forbid_tabs => return Err(String::from("tabs are not allowed")),
             ' ' | '\t' | '\r' => {}
-            '\n' => {
-                self.line += 1;
-            }
+            '\n' => self.newline(),
             '"' => self.string()?,
             _ => {
                 if is_digit(c) {
                     self.number()?;
-                } else if is_alpha(c) {
-                    self.identifier()
+                } else if self.is_identifier_start(c) {
+                    self.identifier()?;
                 } else {
                     return Err(format!("invalid token: {c}"));
                 }
@@ -118,88 +751,299 @@ impl Scanner {
     }
 
     fn advance(&mut self) -> char {
-        let c = self.source[self.current];
-        self.current += 1;
+        let c = self
+            .source
+            .char_at(self.current)
+            .expect("should not advance past the end of source");
+        self.current += S::width(c);
+        self.offset += c.len_utf8();
+        match c {
+            '\n' => {
+                self.line += 1;
+                self.column = 1;
+            }
+            '\t' => {
+                let width = self.options.tab_width.max(1);
+                self.column = (self.column - 1) / width * width + width + 1;
+            }
+            _ => self.column += 1,
+        }
         c
     }
 
     /// ソースコードの終わりに達しているかどうかを判定します。
     fn is_at_end(&self) -> bool {
-        self.current >= self.source.len()
+        self.current >= self.source.end()
+    }
+
+    /// スキャン中のトークンの字句を返す
+    ///
+    /// `resume` で再開した文字列では、前の入力までの字句も含める
+    fn lexeme(&self) -> Cow<'_, str> {
+        let lexeme = self.source.slice(self.start, self.current);
+        if self.carried.is_empty() {
+            return lexeme;
+        }
+        Cow::Owned(self.carried.clone() + &lexeme)
+    }
+
+    /// スキャン中のトークンの字句を、`String` を経由せずに `Token` の字句として返す
+    fn token_lexeme(&self) -> Vec<char> {
+        let lexeme = self.source.chars(self.start, self.current);
+        if self.carried.is_empty() {
+            return lexeme;
+        }
+        self.carried.chars().chain(lexeme).collect()
     }
 
     fn add_token(&mut self, ty: TokenType) {
         self.tokens.push(Token {
             ty,
-            lexeme: self.source[self.start..self.current].to_vec(),
+            lexeme: self.token_lexeme(),
             literal: None,
-            line: self.line,
+            line: self.start_line,
+            column: self.start_column,
+            offset: self.start_offset,
         })
     }
 
     fn add_literal_token(&mut self, ty: TokenType, literal: Literal) {
         self.tokens.push(Token {
             ty,
-            lexeme: self.source[self.start..self.current].to_vec(),
+            lexeme: self.token_lexeme(),
             literal: Some(literal),
-            line: self.line,
+            line: self.start_line,
+            column: self.start_column,
+            offset: self.start_offset,
         })
     }
 
     /// 次の文字が期待したものであった場合に `true`` を返却し、文字を消費する
     /// 期待したものではなかった場合は、文字を消費しない
     fn matches(&mut self, c: char) -> bool {
-        if self.is_at_end() {
+        if self.source.char_at(self.current) != Some(c) {
             return false;
         }
 
-        if self.source[self.current] != c {
-            return false;
-        }
-
-        self.current += 1;
+        self.advance();
         true
     }
 
     fn peek(&self) -> char {
-        if self.is_at_end() {
-            '\0'
-        } else {
-            self.source[self.current]
-        }
+        self.source.char_at(self.current).unwrap_or('\0')
     }
 
     fn peek_next(&self) -> char {
-        if self.current + 1 >= self.source.len() {
-            return '\0';
+        match self.source.char_at(self.current) {
+            Some(c) => self
+                .source
+                .char_at(self.current + S::width(c))
+                .unwrap_or('\0'),
+            None => '\0',
+        }
+    }
+
+    /// 改行を文の終端として扱うモードであれば `TokenType::Newline` を出力する
+    fn newline(&mut self) {
+        if !self.options.newline_terminators || self.nesting > 0 {
+            return;
+        }
+        if matches!(
+            self.tokens.last(),
+            None | Some(Token {
+                ty: TokenType::Newline,
+                ..
+            })
+        ) {
+            return;
         }
 
-        self.source[self.current + 1]
+        self.tokens.push(Token {
+            ty: TokenType::Newline,
+            lexeme: vec!['\n'],
+            literal: None,
+            // `advance` で行番号は次の行に進んでいるため、改行文字がある位置を利用する
+            line: self.start_line,
+            column: self.start_column,
+            offset: self.start_offset,
+        });
+    }
+
+    /// `/*` の直後から `*/` までのブロックコメントを読み飛ばす
+    ///
+    /// ブロックコメントは入れ子にできず、最初に現れた `*/` で終わる
+    fn block_comment(&mut self) -> Result<(), String> {
+        while !(self.peek() == '*' && self.peek_next() == '/') {
+            if self.is_at_end() {
+                self.unterminated = true;
+                self.pending = Some(Pending::BlockComment);
+                return Err(String::from("Unterminated block comment"));
+            }
+            self.advance();
+        }
+
+        // 閉じる `*/` を消費する
+        self.advance();
+        self.advance();
+        Ok(())
+    }
+
+    /// 識別子の先頭に使用できる文字かどうかを判定する
+    fn is_identifier_start(&self, c: char) -> bool {
+        if self.options.normalize_identifiers {
+            c.is_alphabetic()
+        } else {
+            is_alpha(c)
+        }
+    }
+
+    /// 識別子の2文字目以降に使用できる文字かどうかを判定する
+    fn is_identifier_continue(&self, c: char) -> bool {
+        if self.options.normalize_identifiers {
+            c.is_alphanumeric() || is_combining_mark(c)
+        } else {
+            is_alpha_numeric(c)
+        }
     }
 
     fn string(&mut self) -> Result<(), String> {
+        // エスケープシーケンスを解釈した後の文字列を保持する
+        // 字句 `lexeme` にはソースコード上の表記をそのまま残す
+        let literal = String::new();
+        let length = 0;
+        // 不正なエスケープシーケンスがあっても閉じるダブルクォートまでは読み進め、
+        // 文字列の残りが別のトークンとして解析されないようにする
+        let error = None;
+
+        self.string_rest(literal, length, error)
+    }
+
+    /// 文字列リテラルの残りを、閉じるダブルクォートまで読み進める
+    ///
+    /// `literal`・`length`・`error` には、それまでに解析した文字列とその文字数、見つかったエラーを渡す
+    fn string_rest(
+        &mut self,
+        mut literal: String,
+        mut length: usize,
+        mut error: Option<String>,
+    ) -> Result<(), String> {
         while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line += 1;
+            let c = match self.advance() {
+                '\\' => match self.escape() {
+                    Ok(escaped) => escaped,
+                    Err(err) => {
+                        error.get_or_insert(err);
+                        continue;
+                    }
+                },
+                c => c,
+            };
+            // エラーのある文字列はトークンとして出力しないため、値の構築を打ち切る
+            if error.is_some() {
+                continue;
+            }
+            length += 1;
+            match self.check_token_length(length) {
+                Ok(()) => literal.push(c),
+                Err(err) => error = Some(err),
             }
-            self.advance();
         }
 
         if self.is_at_end() {
+            self.unterminated = true;
+            // エラーのある文字列は字句も出力しないため、次の入力へ引き継がない
+            let lexeme = match error {
+                Some(_) => String::new(),
+                None => self.lexeme().into_owned(),
+            };
+            self.pending = Some(Pending::String {
+                lexeme,
+                literal,
+                length,
+                error,
+            });
             return Err(String::from("Unterminated string"));
         }
 
+        // 閉じるダブルクォートを消費する
         self.advance();
 
-        // "..." のうち最初と最後のダブルクォートを無視して、中身の文字列のみ抽出する
-        let literal = self.source[self.start + 1..self.current - 1]
-            .iter()
-            .collect::<String>();
+        if let Some(err) = error {
+            return Err(err);
+        }
+
         self.add_literal_token(TokenType::String, Literal::Str(literal));
 
         Ok(())
     }
 
+    /// `\` の直後から始まるエスケープシーケンスを解釈し、対応する文字を返す
+    ///
+    /// * `\n`, `\t`, `\r`, `\0`, `\\`, `\"` - 対応する制御文字や記号
+    /// * `\xNN` - 2桁の16進数で表したコードポイント
+    /// * `\u{N...}` - 1桁から6桁の16進数で表した任意の Unicode スカラー値
+    fn escape(&mut self) -> Result<char, String> {
+        if self.is_at_end() {
+            return Err(String::from("Unterminated string"));
+        }
+
+        let c = self.advance();
+        let escaped = match c {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '\\' => '\\',
+            '"' => '"',
+            'x' => {
+                let digits = self.hex_digits(2);
+                if digits.len() != 2 {
+                    return Err(format!("invalid escape sequence: \\x{digits}"));
+                }
+                self.code_point(&digits)?
+            }
+            'u' => {
+                if !self.matches('{') {
+                    return Err(String::from(
+                        "invalid escape sequence: expect '{' after \\u",
+                    ));
+                }
+                let digits = self.hex_digits(6);
+                if !self.matches('}') {
+                    return Err(format!(
+                        "invalid escape sequence: expect '}}' after \\u{{{digits}"
+                    ));
+                }
+                if digits.is_empty() {
+                    return Err(String::from("invalid escape sequence: empty \\u{}"));
+                }
+                self.code_point(&digits)?
+            }
+            _ => return Err(format!("invalid escape sequence: \\{c}")),
+        };
+
+        Ok(escaped)
+    }
+
+    /// 最大 `max` 文字までの16進数の数字を消費して返す
+    fn hex_digits(&mut self, max: usize) -> String {
+        let mut digits = String::new();
+        while digits.len() < max && self.peek().is_ascii_hexdigit() {
+            digits.push(self.advance());
+        }
+        digits
+    }
+
+    /// 16進数で表されたコードポイントを文字に変換する
+    ///
+    /// サロゲートなど Unicode スカラー値ではないコードポイントはエラーとする
+    fn code_point(&self, digits: &str) -> Result<char, String> {
+        u32::from_str_radix(digits, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| format!("invalid code point: {digits}"))
+    }
+
     fn number(&mut self) -> Result<(), String> {
         while is_digit(self.peek()) {
             self.advance();
@@ -212,29 +1056,83 @@ impl Scanner {
             }
         }
 
-        let value = self.source[self.start..self.current]
-            .iter()
-            .collect::<String>()
+        self.check_token_length(self.column - self.start_column)?;
+        let lexeme = self.lexeme();
+        let value: f64 = lexeme
             .parse()
             .map_err(|err| format!("invalid number: {err}"))?;
+        // 2^53 を超える整数は f64 で正確に表せず、黙って丸められてしまうため警告する
+        let exact = lexeme.contains('.')
+            || lexeme
+                .parse::<u128>()
+                .is_ok_and(|integer| value as u128 == integer);
+        if !exact {
+            let message = format!(
+                "integer literal {lexeme} cannot be represented exactly and becomes {value}"
+            );
+            self.warnings.push(ScanError {
+                line: self.start_line,
+                column: self.start_column,
+                message,
+            });
+        }
         self.add_literal_token(TokenType::Number, Literal::Number(value));
 
         Ok(())
     }
 
-    fn identifier(&mut self) {
-        while is_alpha_numeric(self.peek()) {
+    fn identifier(&mut self) -> Result<(), String> {
+        while self.is_identifier_continue(self.peek()) {
             self.advance();
         }
+        self.check_token_length(self.column - self.start_column)?;
 
-        let literal = self.source[self.start..self.current]
-            .iter()
-            .collect::<String>();
-        match match_keywords(&literal) {
+        let literal = if self.options.normalize_identifiers {
+            self.lexeme().nfc().collect()
+        } else {
+            self.lexeme().into_owned()
+        };
+        let keyword = if self.options.case_insensitive_keywords {
+            Cow::Owned(literal.to_lowercase())
+        } else {
+            Cow::Borrowed(literal.as_str())
+        };
+        let ty = match self.options.keywords.get(keyword.as_ref()) {
+            Some(TokenType::Identifier) => None,
+            Some(ty) => Some(ty.clone()),
+            None => match_keywords(&keyword),
+        };
+        match ty {
             Some(ty) => self.add_token(ty),
-            None => self.add_literal_token(TokenType::Identifier, Literal::Identifier(literal)),
+            None => {
+                let name = self.intern(literal);
+                self.add_literal_token(TokenType::Identifier, Literal::Identifier(name))
+            }
+        }
+
+        Ok(())
+    }
+
+    /// スキャン中のトークンの文字数 `length` が `max_token_length` を超えていないか確認する
+    ///
+    /// 識別子と数値はタブや改行を含まないため、字句を生成せずに列番号の差から文字数を求めて渡す
+    fn check_token_length(&self, length: usize) -> Result<(), String> {
+        match self.options.max_token_length {
+            Some(max) if length > max => Err(String::from("token too long")),
+            _ => Ok(()),
         }
     }
+
+    /// 識別子の名前をインターンし、以前に同じ名前が現れていればその文字列を共有する
+    fn intern(&mut self, name: String) -> Rc<str> {
+        if let Some(interned) = self.identifiers.get(name.as_str()) {
+            return Rc::clone(interned);
+        }
+
+        let interned: Rc<str> = Rc::from(name);
+        self.identifiers.insert(Rc::clone(&interned));
+        interned
+    }
 }
 
 fn is_digit(c: char) -> bool {
@@ -251,15 +1149,22 @@ fn is_alpha_numeric(c: char) -> bool {
 
 #[cfg(test)]
 mod tests {
+    use std::{collections::HashMap, rc::Rc};
+
     use crate::{
-        scanner::scan_tokens,
+        scanner::{
+            scan_reader, scan_sources, scan_tokens, scan_tokens_borrowed, scan_tokens_collecting,
+            scan_tokens_with, scan_tokens_with_warnings, tokenize_expr, ExprStatus, FileScanError,
+            ScanError, ScanOptions,
+        },
         token::TokenType,
         token::{Literal, Token},
     };
 
     #[test]
     fn test_one_char_token() {
-        let input = "{}(),.-+;/*";
+        // `/*` はブロックコメントの開始となるため、`/` と `*` の間に空白を入れる
+        let input = "{}(),.-+;/ *";
 
         let expected = vec![
             Token {
@@ -267,72 +1172,96 @@ mod tests {
                 lexeme: vec!['{'],
                 literal: None,
                 line: 1,
+                column: 1,
+                offset: 0,
             },
             Token {
                 ty: TokenType::RBrace,
                 lexeme: vec!['}'],
                 literal: None,
                 line: 1,
+                column: 2,
+                offset: 1,
             },
             Token {
                 ty: TokenType::LParan,
                 lexeme: vec!['('],
                 literal: None,
                 line: 1,
+                column: 3,
+                offset: 2,
             },
             Token {
                 ty: TokenType::RParan,
                 lexeme: vec![')'],
                 literal: None,
                 line: 1,
+                column: 4,
+                offset: 3,
             },
             Token {
                 ty: TokenType::Comma,
                 lexeme: vec![','],
                 literal: None,
                 line: 1,
+                column: 5,
+                offset: 4,
             },
             Token {
                 ty: TokenType::Dot,
                 lexeme: vec!['.'],
                 literal: None,
                 line: 1,
+                column: 6,
+                offset: 5,
             },
             Token {
                 ty: TokenType::Minus,
                 lexeme: vec!['-'],
                 literal: None,
                 line: 1,
+                column: 7,
+                offset: 6,
             },
             Token {
                 ty: TokenType::Plus,
                 lexeme: vec!['+'],
                 literal: None,
                 line: 1,
+                column: 8,
+                offset: 7,
             },
             Token {
                 ty: TokenType::SemiColon,
                 lexeme: vec![';'],
                 literal: None,
                 line: 1,
+                column: 9,
+                offset: 8,
             },
             Token {
                 ty: TokenType::Slash,
                 lexeme: vec!['/'],
                 literal: None,
                 line: 1,
+                column: 10,
+                offset: 9,
             },
             Token {
                 ty: TokenType::Star,
                 lexeme: vec!['*'],
                 literal: None,
                 line: 1,
+                column: 12,
+                offset: 11,
             },
             Token {
                 ty: TokenType::Eof,
                 lexeme: vec![],
                 literal: None,
                 line: 1,
+                column: 13,
+                offset: 12,
             },
         ];
 
@@ -343,7 +1272,7 @@ mod tests {
             "トークンの数が期待と異なります。"
         );
 
-        for (expected_token, actual_token) in expected.into_iter().zip(tokens.into_iter()) {
+        for (expected_token, actual_token) in expected.into_iter().zip(tokens) {
             assert_eq!(
                 expected_token, actual_token,
                 "期待するトークンと実際のトークンが異なります。"
@@ -361,54 +1290,72 @@ mod tests {
                 lexeme: vec!['!'],
                 literal: None,
                 line: 1,
+                column: 1,
+                offset: 0,
             },
             Token {
                 ty: TokenType::BangEqual,
                 lexeme: vec!['!', '='],
                 literal: None,
                 line: 1,
+                column: 2,
+                offset: 1,
             },
             Token {
                 ty: TokenType::EqualEqual,
                 lexeme: vec!['=', '='],
                 literal: None,
                 line: 1,
+                column: 4,
+                offset: 3,
             },
             Token {
                 ty: TokenType::Equal,
                 lexeme: vec!['='],
                 literal: None,
                 line: 1,
+                column: 6,
+                offset: 5,
             },
             Token {
                 ty: TokenType::Greater,
                 lexeme: vec!['>'],
                 literal: None,
                 line: 1,
+                column: 7,
+                offset: 6,
             },
             Token {
                 ty: TokenType::GreaterEqual,
                 lexeme: vec!['>', '='],
                 literal: None,
                 line: 1,
+                column: 8,
+                offset: 7,
             },
             Token {
                 ty: TokenType::Less,
                 lexeme: vec!['<'],
                 literal: None,
                 line: 1,
+                column: 10,
+                offset: 9,
             },
             Token {
                 ty: TokenType::LessEqual,
                 lexeme: vec!['<', '='],
                 literal: None,
                 line: 1,
+                column: 11,
+                offset: 10,
             },
             Token {
                 ty: TokenType::Eof,
                 lexeme: vec![],
                 literal: None,
                 line: 1,
+                column: 13,
+                offset: 12,
             },
         ];
 
@@ -419,7 +1366,7 @@ mod tests {
             "トークンの数が期待と異なります。"
         );
 
-        for (expected_token, actual_token) in expected.into_iter().zip(tokens.into_iter()) {
+        for (expected_token, actual_token) in expected.into_iter().zip(tokens) {
             assert_eq!(
                 expected_token, actual_token,
                 "期待するトークンと実際のトークンが異なります。"
@@ -441,18 +1388,24 @@ mod tests {
                 lexeme: vec!['('],
                 literal: None,
                 line: 2,
+                column: 9,
+                offset: 9,
             },
             Token {
                 ty: TokenType::RParan,
                 lexeme: vec![')'],
                 literal: None,
                 line: 4,
+                column: 9,
+                offset: 54,
             },
             Token {
                 ty: TokenType::Eof,
                 lexeme: vec![],
                 literal: None,
                 line: 5,
+                column: 9,
+                offset: 64,
             },
         ];
 
@@ -463,7 +1416,7 @@ mod tests {
             "トークンの数が期待と異なります。"
         );
 
-        for (expected_token, actual_token) in expected.into_iter().zip(tokens.into_iter()) {
+        for (expected_token, actual_token) in expected.into_iter().zip(tokens) {
             assert_eq!(
                 expected_token, actual_token,
                 "期待するトークンと実際のトークンが異なります。"
@@ -485,12 +1438,16 @@ mod tests {
                 ],
                 literal: Some(Literal::Str("hello_world".to_string())),
                 line: 2,
+                column: 9,
+                offset: 9,
             },
             Token {
                 ty: TokenType::Eof,
                 lexeme: vec![],
                 literal: None,
                 line: 3,
+                column: 9,
+                offset: 31,
             },
         ];
 
@@ -501,7 +1458,7 @@ mod tests {
             "トークンの数が期待と異なります。"
         );
 
-        for (expected_token, actual_token) in expected.into_iter().zip(tokens.into_iter()) {
+        for (expected_token, actual_token) in expected.into_iter().zip(tokens) {
             assert_eq!(
                 expected_token, actual_token,
                 "期待するトークンと実際のトークンが異なります。"
@@ -521,12 +1478,16 @@ mod tests {
                 lexeme: vec!['0', '.', '1', '4', '5'],
                 literal: Some(Literal::Number(0.145)),
                 line: 2,
+                column: 9,
+                offset: 9,
             },
             Token {
                 ty: TokenType::Eof,
                 lexeme: vec![],
                 literal: None,
                 line: 3,
+                column: 9,
+                offset: 23,
             },
         ];
 
@@ -537,7 +1498,7 @@ mod tests {
             "トークンの数が期待と異なります。"
         );
 
-        for (expected_token, actual_token) in expected.into_iter().zip(tokens.into_iter()) {
+        for (expected_token, actual_token) in expected.into_iter().zip(tokens) {
             assert_eq!(
                 expected_token, actual_token,
                 "期待するトークンと実際のトークンが異なります。"
@@ -557,36 +1518,48 @@ mod tests {
                 lexeme: vec!['v', 'a', 'r'],
                 literal: None,
                 line: 2,
+                column: 9,
+                offset: 9,
             },
             Token {
                 ty: TokenType::Identifier,
                 lexeme: vec!['f', 'i', 'v', 'e'],
-                literal: Some(Literal::Identifier("five".to_string())),
+                literal: Some(Literal::Identifier("five".into())),
                 line: 2,
+                column: 13,
+                offset: 13,
             },
             Token {
                 ty: TokenType::Equal,
                 lexeme: vec!['='],
                 literal: None,
                 line: 2,
+                column: 18,
+                offset: 18,
             },
             Token {
                 ty: TokenType::Number,
                 lexeme: vec!['5'],
                 literal: Some(Literal::Number(5.0)),
                 line: 2,
+                column: 20,
+                offset: 20,
             },
             Token {
                 ty: TokenType::SemiColon,
                 lexeme: vec![';'],
                 literal: None,
                 line: 2,
+                column: 21,
+                offset: 21,
             },
             Token {
                 ty: TokenType::Eof,
                 lexeme: vec![],
                 literal: None,
                 line: 3,
+                column: 9,
+                offset: 31,
             },
         ];
 
@@ -597,7 +1570,7 @@ mod tests {
             "トークンの数が期待と異なります。"
         );
 
-        for (expected_token, actual_token) in expected.into_iter().zip(tokens.into_iter()) {
+        for (expected_token, actual_token) in expected.into_iter().zip(tokens) {
             assert_eq!(
                 expected_token, actual_token,
                 "期待するトークンと実際のトークンが異なります。"
@@ -623,186 +1596,248 @@ mod tests {
                 lexeme: vec!['v', 'a', 'r'],
                 literal: None,
                 line: 2,
+                column: 9,
+                offset: 9,
             },
             Token {
                 ty: TokenType::Identifier,
                 lexeme: vec!['c', 'o', 'n', 'd', 'A', 'd', 'd'],
-                literal: Some(Literal::Identifier("condAdd".to_string())),
+                literal: Some(Literal::Identifier("condAdd".into())),
                 line: 2,
+                column: 13,
+                offset: 13,
             },
             Token {
                 ty: TokenType::Equal,
                 lexeme: vec!['='],
                 literal: None,
                 line: 2,
+                column: 21,
+                offset: 21,
             },
             Token {
                 ty: TokenType::Fun,
                 lexeme: vec!['f', 'u', 'n'],
                 literal: None,
                 line: 2,
+                column: 23,
+                offset: 23,
             },
             Token {
                 ty: TokenType::LParan,
                 lexeme: vec!['('],
                 literal: None,
                 line: 2,
+                column: 26,
+                offset: 26,
             },
             Token {
                 ty: TokenType::Identifier,
                 lexeme: vec!['a'],
-                literal: Some(Literal::Identifier("a".to_string())),
+                literal: Some(Literal::Identifier("a".into())),
                 line: 2,
+                column: 27,
+                offset: 27,
             },
             Token {
                 ty: TokenType::Comma,
                 lexeme: vec![','],
                 literal: None,
                 line: 2,
+                column: 28,
+                offset: 28,
             },
             Token {
                 ty: TokenType::Identifier,
                 lexeme: vec!['b'],
-                literal: Some(Literal::Identifier("b".to_string())),
+                literal: Some(Literal::Identifier("b".into())),
                 line: 2,
+                column: 30,
+                offset: 30,
             },
             Token {
                 ty: TokenType::RParan,
                 lexeme: vec![')'],
                 literal: None,
                 line: 2,
+                column: 31,
+                offset: 31,
             },
             Token {
                 ty: TokenType::LBrace,
                 lexeme: vec!['{'],
                 literal: None,
                 line: 2,
+                column: 33,
+                offset: 33,
             },
             Token {
                 ty: TokenType::If,
                 lexeme: vec!['i', 'f'],
                 literal: None,
                 line: 3,
+                column: 13,
+                offset: 47,
             },
             Token {
                 ty: TokenType::LParan,
                 lexeme: vec!['('],
                 literal: None,
                 line: 3,
+                column: 16,
+                offset: 50,
             },
             Token {
                 ty: TokenType::Identifier,
                 lexeme: vec!['a'],
-                literal: Some(Literal::Identifier("a".to_string())),
+                literal: Some(Literal::Identifier("a".into())),
                 line: 3,
+                column: 17,
+                offset: 51,
             },
             Token {
                 ty: TokenType::Greater,
                 lexeme: vec!['>'],
                 literal: None,
                 line: 3,
+                column: 19,
+                offset: 53,
             },
             Token {
                 ty: TokenType::Number,
                 lexeme: vec!['0'],
                 literal: Some(Literal::Number(0_f64)),
                 line: 3,
+                column: 21,
+                offset: 55,
             },
             Token {
                 ty: TokenType::RParan,
                 lexeme: vec![')'],
                 literal: None,
                 line: 3,
+                column: 22,
+                offset: 56,
             },
             Token {
                 ty: TokenType::LBrace,
                 lexeme: vec!['{'],
                 literal: None,
                 line: 3,
+                column: 24,
+                offset: 58,
             },
             Token {
                 ty: TokenType::Return,
                 lexeme: vec!['r', 'e', 't', 'u', 'r', 'n'],
                 literal: None,
                 line: 4,
+                column: 17,
+                offset: 76,
             },
             Token {
                 ty: TokenType::Identifier,
                 lexeme: vec!['a'],
-                literal: Some(Literal::Identifier("a".to_string())),
+                literal: Some(Literal::Identifier("a".into())),
                 line: 4,
+                column: 24,
+                offset: 83,
             },
             Token {
                 ty: TokenType::Plus,
                 lexeme: vec!['+'],
                 literal: None,
                 line: 4,
+                column: 26,
+                offset: 85,
             },
             Token {
                 ty: TokenType::Identifier,
                 lexeme: vec!['b'],
-                literal: Some(Literal::Identifier("b".to_string())),
+                literal: Some(Literal::Identifier("b".into())),
                 line: 4,
+                column: 28,
+                offset: 87,
             },
             Token {
                 ty: TokenType::SemiColon,
                 lexeme: vec![';'],
                 literal: None,
                 line: 4,
+                column: 29,
+                offset: 88,
             },
             Token {
                 ty: TokenType::RBrace,
                 lexeme: vec!['}'],
                 literal: None,
                 line: 5,
+                column: 13,
+                offset: 102,
             },
             Token {
                 ty: TokenType::Else,
                 lexeme: vec!['e', 'l', 's', 'e'],
                 literal: None,
                 line: 5,
+                column: 15,
+                offset: 104,
             },
             Token {
                 ty: TokenType::LBrace,
                 lexeme: vec!['{'],
                 literal: None,
                 line: 5,
+                column: 20,
+                offset: 109,
             },
             Token {
                 ty: TokenType::Return,
                 lexeme: vec!['r', 'e', 't', 'u', 'r', 'n'],
                 literal: None,
                 line: 6,
+                column: 17,
+                offset: 127,
             },
             Token {
                 ty: TokenType::Identifier,
                 lexeme: vec!['a'],
-                literal: Some(Literal::Identifier("a".to_string())),
+                literal: Some(Literal::Identifier("a".into())),
                 line: 6,
+                column: 24,
+                offset: 134,
             },
             Token {
                 ty: TokenType::SemiColon,
                 lexeme: vec![';'],
                 literal: None,
                 line: 6,
+                column: 25,
+                offset: 135,
             },
             Token {
                 ty: TokenType::RBrace,
                 lexeme: vec!['}'],
                 literal: None,
                 line: 7,
+                column: 13,
+                offset: 149,
             },
             Token {
                 ty: TokenType::RBrace,
                 lexeme: vec!['}'],
                 literal: None,
                 line: 8,
+                column: 9,
+                offset: 159,
             },
             Token {
                 ty: TokenType::Eof,
                 lexeme: vec![],
                 literal: None,
                 line: 9,
+                column: 9,
+                offset: 169,
             },
         ];
 
@@ -813,11 +1848,701 @@ mod tests {
             "トークンの数が期待と異なります。"
         );
 
-        for (expected_token, actual_token) in expected.into_iter().zip(tokens.into_iter()) {
+        for (expected_token, actual_token) in expected.into_iter().zip(tokens) {
             assert_eq!(
                 expected_token, actual_token,
                 "期待するトークンと実際のトークンが異なります。"
             );
         }
     }
+
+    #[test]
+    fn test_string_escape() {
+        let input = r#""a\tb\"c\x41\u{1F600}""#;
+
+        let tokens = scan_tokens(input).expect("スキャンに失敗しました。");
+        assert_eq!(
+            Token {
+                ty: TokenType::String,
+                lexeme: input.chars().collect(),
+                literal: Some(Literal::Str("a\tb\"cA😀".to_string())),
+                line: 1,
+                column: 1,
+                offset: 0,
+            },
+            tokens[0]
+        );
+    }
+
+    #[test]
+    fn test_invalid_string_escape() {
+        let error = scan_tokens(r#""\u{D800}""#).expect_err("サロゲートはエラーになるべきです。");
+        assert_eq!("invalid code point: D800", error.message);
+
+        let error = scan_tokens(r#""\u{}""#).expect_err("空の \\u{} はエラーになるべきです。");
+        assert_eq!("invalid escape sequence: empty \\u{}", error.message);
+
+        let error = scan_tokens(r#""\q""#).expect_err("未知のエスケープはエラーになるべきです。");
+        assert_eq!("invalid escape sequence: \\q", error.message);
+    }
+
+    #[test]
+    fn test_borrowed_scanner() {
+        let input = r#"
+        // 日本語のコメント
+        var name = "けいすけ\u{1F600}";
+        var five = 5.5 ** 2 >= [1, 2][0];
+        "#;
+
+        let expected = scan_tokens(input).expect("スキャンに失敗しました。");
+        let tokens = scan_tokens_borrowed(input).expect("スキャンに失敗しました。");
+        assert_eq!(expected, tokens);
+
+        assert_eq!(
+            scan_tokens("\"unterminated"),
+            scan_tokens_borrowed("\"unterminated")
+        );
+    }
+
+    #[test]
+    fn test_case_insensitive_keywords() {
+        let input = "IF While myVar";
+
+        let tokens = scan_tokens(input).expect("スキャンに失敗しました。");
+        assert_eq!(
+            vec![
+                TokenType::Identifier,
+                TokenType::Identifier,
+                TokenType::Identifier,
+                TokenType::Eof
+            ],
+            tokens.into_iter().map(|token| token.ty).collect::<Vec<_>>()
+        );
+
+        let options = ScanOptions {
+            case_insensitive_keywords: true,
+            ..Default::default()
+        };
+        let tokens = scan_tokens_with(input, &options).expect("スキャンに失敗しました。");
+        assert_eq!(
+            Token {
+                ty: TokenType::If,
+                lexeme: vec!['I', 'F'],
+                literal: None,
+                line: 1,
+                column: 1,
+                offset: 0,
+            },
+            tokens[0]
+        );
+        assert_eq!(TokenType::While, tokens[1].ty);
+        assert_eq!(Some(Literal::Identifier("myVar".into())), tokens[2].literal);
+    }
+
+    #[test]
+    fn test_scan_tokens_collecting() {
+        let input = r#"
+        var a = @;
+        var b = "\q" + #;
+        "#;
+
+        let (tokens, errors) = scan_tokens_collecting(input, &ScanOptions::default());
+        assert_eq!(
+            vec![
+                ScanError {
+                    line: 2,
+                    column: 17,
+                    message: "invalid token: @".to_string(),
+                },
+                ScanError {
+                    line: 3,
+                    column: 17,
+                    message: "invalid escape sequence: \\q".to_string(),
+                },
+                ScanError {
+                    line: 3,
+                    column: 24,
+                    message: "invalid token: #".to_string(),
+                },
+            ],
+            errors
+        );
+        assert_eq!(
+            vec![
+                TokenType::Var,
+                TokenType::Identifier,
+                TokenType::Equal,
+                TokenType::SemiColon,
+                TokenType::Var,
+                TokenType::Identifier,
+                TokenType::Equal,
+                TokenType::Plus,
+                TokenType::SemiColon,
+                TokenType::Eof,
+            ],
+            tokens.into_iter().map(|token| token.ty).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_scan_error_column() {
+        // マルチバイト文字も1文字として数える
+        let input = "\"あい\"\n  1 + @";
+
+        let err = scan_tokens(input).expect_err("スキャンはエラーになるべきです。");
+        assert_eq!((2, 7), (err.line, err.column));
+        assert_eq!(Err(err), scan_tokens_borrowed(input));
+    }
+
+    #[test]
+    fn test_newline_terminators() {
+        let input = "\nvar a = (1 +\n  2)\n\nprint [a,\n a]\n";
+
+        let tokens = scan_tokens(input).expect("スキャンに失敗しました。");
+        assert!(tokens.iter().all(|token| token.ty != TokenType::Newline));
+
+        let options = ScanOptions {
+            newline_terminators: true,
+            ..Default::default()
+        };
+        let tokens = scan_tokens_with(input, &options).expect("スキャンに失敗しました。");
+        // 改行トークンは終端した行の行番号を持つ
+        assert_eq!((3, 6), (tokens[8].line, tokens[15].line));
+        assert_eq!(
+            vec![
+                TokenType::Var,
+                TokenType::Identifier,
+                TokenType::Equal,
+                TokenType::LParan,
+                TokenType::Number,
+                TokenType::Plus,
+                TokenType::Number,
+                TokenType::RParan,
+                TokenType::Newline,
+                TokenType::Print,
+                TokenType::LBracket,
+                TokenType::Identifier,
+                TokenType::Comma,
+                TokenType::Identifier,
+                TokenType::RBracket,
+                TokenType::Newline,
+                TokenType::Eof,
+            ],
+            tokens.into_iter().map(|token| token.ty).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_skip_shebang() {
+        let tokens = scan_tokens("#!/usr/bin/env lox\nprint 1;").expect("スキャンに失敗しました。");
+        assert_eq!(
+            Token {
+                ty: TokenType::Print,
+                lexeme: vec!['p', 'r', 'i', 'n', 't'],
+                literal: None,
+                line: 2,
+                column: 1,
+                offset: 19,
+            },
+            tokens[0]
+        );
+        assert_eq!(
+            vec![
+                TokenType::Print,
+                TokenType::Number,
+                TokenType::SemiColon,
+                TokenType::Eof
+            ],
+            tokens.into_iter().map(|token| token.ty).collect::<Vec<_>>()
+        );
+
+        let err = scan_tokens("print 1;\n#!/usr/bin/env lox")
+            .expect_err("スキャンはエラーになるべきです。");
+        assert_eq!("invalid token: #", err.message);
+    }
+
+    #[test]
+    fn test_unterminated_string_position() {
+        let input = "1 +\n2 +\n  \"abc\ndef\n";
+
+        let err = scan_tokens(input).expect_err("スキャンはエラーになるべきです。");
+        assert_eq!(
+            ScanError {
+                line: 3,
+                column: 3,
+                message: "Unterminated string".to_string(),
+            },
+            err
+        );
+        assert!(err.to_string().starts_with("[line 3]"));
+    }
+
+    #[test]
+    fn test_scan_reader() {
+        let input =
+            "#!/usr/bin/env lox\n[1, /* multi\n\tline\n */ \"multi\nline\\q\" @\n  9007199254740993] + \"unterminated\nstring\n";
+
+        for options in [
+            ScanOptions::default(),
+            ScanOptions {
+                newline_terminators: true,
+                ..Default::default()
+            },
+        ] {
+            let expected = scan_tokens_with_warnings(input, &options);
+            let actual = scan_reader(input.as_bytes(), &options).expect("読み込みに失敗しました。");
+            assert_eq!(expected, actual);
+        }
+    }
+
+    #[test]
+    fn test_scan_reader_resumes_pending_tokens() {
+        for input in [
+            "1 + \"a\n\tb\n\\u{1F600}\" + 2\n",
+            "/* unterminated\ncomment\n",
+            "1 /* a\n*\n*/ 2 /* b */\n3",
+            "\"no newline at end",
+            "\"bad \\q\nescape\" @",
+        ] {
+            let expected = scan_tokens_with_warnings(input, &ScanOptions::default());
+            let actual = scan_reader(input.as_bytes(), &ScanOptions::default())
+                .expect("読み込みに失敗しました。");
+            assert_eq!(expected, actual, "{input:?}");
+        }
+
+        // 括弧の外側では、コメントを閉じた行の改行も文の終端となる
+        let options = ScanOptions {
+            newline_terminators: true,
+            ..Default::default()
+        };
+        for input in ["a /* x\n*/\nb", "a /* x\n*/ // c\nb"] {
+            let expected = scan_tokens_with_warnings(input, &options);
+            assert_eq!(
+                vec![
+                    TokenType::Identifier,
+                    TokenType::Newline,
+                    TokenType::Identifier,
+                    TokenType::Eof
+                ],
+                expected
+                    .tokens
+                    .iter()
+                    .map(|token| token.ty.clone())
+                    .collect::<Vec<_>>()
+            );
+            let actual = scan_reader(input.as_bytes(), &options).expect("読み込みに失敗しました。");
+            assert_eq!(expected, actual, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn test_slash_tokens() {
+        let types = |input: &str| {
+            scan_tokens(input)
+                .expect("スキャンに失敗しました。")
+                .into_iter()
+                .map(|token| token.ty)
+                .collect::<Vec<_>>()
+        };
+
+        use TokenType::*;
+        assert_eq!(vec![Identifier, Slash, Identifier, Eof], types("a / b"));
+        assert_eq!(
+            vec![Identifier, SlashEqual, Identifier, Eof],
+            types("a /= b")
+        );
+        assert_eq!(vec![Identifier, Eof], types("a // comment / b"));
+        assert_eq!(vec![Identifier, Identifier, Eof], types("a /* c */ b"));
+        assert_eq!(vec![Identifier, Identifier, Eof], types("a /* c * / **/ b"));
+
+        let tokens = scan_tokens("a /* multi\nline\n*/ b").expect("スキャンに失敗しました。");
+        assert_eq!(3, tokens[1].line);
+
+        let err =
+            scan_tokens("a\n /* unterminated\n").expect_err("スキャンはエラーになるべきです。");
+        assert_eq!(
+            ScanError {
+                line: 2,
+                column: 2,
+                message: "Unterminated block comment".to_string(),
+            },
+            err
+        );
+    }
+
+    #[test]
+    fn test_tab_width() {
+        let tokens = scan_tokens("\tx").expect("スキャンに失敗しました。");
+        assert_eq!(2, tokens[0].column);
+
+        let options = ScanOptions {
+            tab_width: 4,
+            ..Default::default()
+        };
+        let tokens = scan_tokens_with("\tx", &options).expect("スキャンに失敗しました。");
+        assert_eq!(5, tokens[0].column);
+        // タブ文字は次のタブ位置まで進める
+        let tokens = scan_tokens_with("ab\tx\t\ty", &options).expect("スキャンに失敗しました。");
+        assert_eq!((5, 13), (tokens[1].column, tokens[2].column));
+
+        let err =
+            scan_tokens_with("\t\t@", &options).expect_err("スキャンはエラーになるべきです。");
+        assert_eq!(9, err.column);
+    }
+
+    #[test]
+    fn test_forbid_tabs() {
+        let input = "var a =\n\t1; // \tcomment";
+
+        let tokens = scan_tokens(input).expect("スキャンに失敗しました。");
+        assert_eq!(TokenType::Number, tokens[3].ty);
+
+        let options = ScanOptions {
+            forbid_tabs: true,
+            ..Default::default()
+        };
+        let (tokens, errors) = scan_tokens_collecting(input, &options);
+        assert_eq!(
+            vec![ScanError {
+                line: 2,
+                column: 1,
+                message: "tabs are not allowed".to_string(),
+            }],
+            errors
+        );
+        assert_eq!(TokenType::Number, tokens[3].ty);
+    }
+
+    #[test]
+    fn test_normalize_identifiers() {
+        // 合成済みの `é` (U+00E9) と、`e` に結合文字 (U+0301) を続けた表記
+        let input = "caf\u{e9} cafe\u{301}";
+
+        let err = scan_tokens(input).expect_err("スキャンはエラーになるべきです。");
+        assert_eq!("invalid token: é", err.message);
+
+        let options = ScanOptions {
+            normalize_identifiers: true,
+            ..Default::default()
+        };
+        let tokens = scan_tokens_with(input, &options).expect("スキャンに失敗しました。");
+        let expected = Some(Literal::Identifier("caf\u{e9}".into()));
+        assert_eq!(expected, tokens[0].literal);
+        assert_eq!(expected, tokens[1].literal);
+        // 字句はソースコード上の表記のまま
+        assert_eq!(5, tokens[1].lexeme.len());
+    }
+
+    #[test]
+    fn test_tokenize_expr() {
+        assert_eq!(ExprStatus::Complete, tokenize_expr("(1 + 2)"));
+        assert_eq!(ExprStatus::Complete, tokenize_expr("[1, {\"a\": 2}][0]"));
+
+        assert_eq!(ExprStatus::NeedsMore, tokenize_expr("(1 +"));
+        assert_eq!(ExprStatus::NeedsMore, tokenize_expr("1 +"));
+        assert_eq!(ExprStatus::NeedsMore, tokenize_expr("[1, 2,"));
+        assert_eq!(ExprStatus::NeedsMore, tokenize_expr("\"unterminated"));
+        assert_eq!(ExprStatus::NeedsMore, tokenize_expr("/* unterminated"));
+
+        assert_eq!(ExprStatus::Invalid, tokenize_expr("@"));
+        assert_eq!(ExprStatus::Invalid, tokenize_expr("(1 + 2]"));
+        assert_eq!(ExprStatus::Invalid, tokenize_expr("1 + 2)"));
+        assert_eq!(ExprStatus::Invalid, tokenize_expr("@ + \"unterminated"));
+
+        assert_eq!(ExprStatus::Empty, tokenize_expr(""));
+        assert_eq!(ExprStatus::Empty, tokenize_expr("  // comment"));
+        assert_eq!(ExprStatus::Empty, tokenize_expr("/* comment */\n"));
+    }
+
+    #[test]
+    fn test_intern_identifiers() {
+        let input = "a + b\n* a";
+
+        for tokens in [
+            scan_tokens(input).expect("スキャンに失敗しました。"),
+            scan_reader(input.as_bytes(), &ScanOptions::default())
+                .expect("読み込みに失敗しました。")
+                .tokens,
+        ] {
+            let (Some(Literal::Identifier(first)), Some(Literal::Identifier(second))) =
+                (&tokens[0].literal, &tokens[4].literal)
+            else {
+                panic!("tokens should be identifiers: {tokens:?}");
+            };
+            assert_eq!("a", &**first);
+            assert!(Rc::ptr_eq(first, second));
+        }
+    }
+
+    #[test]
+    fn test_in_keyword() {
+        let tokens = scan_tokens("in index inner").expect("スキャンに失敗しました。");
+        assert_eq!(
+            vec![
+                TokenType::In,
+                TokenType::Identifier,
+                TokenType::Identifier,
+                TokenType::Eof
+            ],
+            tokens.into_iter().map(|token| token.ty).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_empty_input() {
+        for input in ["", " \t\r\n\n", "// comment only"] {
+            let tokens = scan_tokens(input).expect("スキャンに失敗しました。");
+            assert_eq!(1, tokens.len());
+            assert_eq!(TokenType::Eof, tokens[0].ty);
+        }
+    }
+
+    #[test]
+    fn test_large_integer_warning() {
+        let input = "9007199254740992 + 9007199254740993 + 0.5";
+
+        let output = scan_tokens_with_warnings(input, &ScanOptions::default());
+        assert!(output.errors.is_empty());
+        assert_eq!(
+            vec![ScanError {
+                line: 1,
+                column: 20,
+                message: "integer literal 9007199254740993 cannot be represented exactly and becomes 9007199254740992".to_string(),
+            }],
+            output.warnings
+        );
+        assert_eq!(
+            Some(Literal::Number(9007199254740992.0)),
+            output.tokens[2].literal
+        );
+
+        let output = scan_tokens_with_warnings("1e400", &ScanOptions::default());
+        assert!(output.warnings.is_empty());
+        let output = scan_tokens_with_warnings(
+            "1000000000000000000000000000000000000000",
+            &ScanOptions::default(),
+        );
+        assert_eq!(1, output.warnings.len());
+    }
+
+    #[test]
+    fn test_max_token_length() {
+        let options = ScanOptions {
+            max_token_length: Some(4),
+            ..Default::default()
+        };
+
+        let (tokens, errors) = scan_tokens_collecting("abcd abcde 12345 \"abcde\" 1.25", &options);
+        assert_eq!(
+            vec![
+                ScanError {
+                    line: 1,
+                    column: 6,
+                    message: "token too long".to_string(),
+                },
+                ScanError {
+                    line: 1,
+                    column: 12,
+                    message: "token too long".to_string(),
+                },
+                ScanError {
+                    line: 1,
+                    column: 18,
+                    message: "token too long".to_string(),
+                },
+            ],
+            errors
+        );
+        assert_eq!(
+            vec![TokenType::Identifier, TokenType::Number, TokenType::Eof],
+            tokens.into_iter().map(|token| token.ty).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_max_token_length_boundary() {
+        let options = ScanOptions {
+            max_token_length: Some(3),
+            ..Default::default()
+        };
+
+        for input in ["abc", "123", "1.5", "\"abc\"", "\"\\n\\t\\\\\""] {
+            let (_, errors) = scan_tokens_collecting(input, &options);
+            assert!(errors.is_empty(), "{input:?}");
+        }
+        for input in ["abcd", "1234", "1.25", "\"abcd\"", "\"ab\ncd\""] {
+            let (tokens, errors) = scan_tokens_collecting(input, &options);
+            assert_eq!(
+                vec!["token too long"],
+                errors
+                    .iter()
+                    .map(|error| error.message.as_str())
+                    .collect::<Vec<_>>(),
+                "{input:?}"
+            );
+            assert_eq!(1, tokens.len(), "{input:?}");
+
+            let expected = scan_tokens_with_warnings(input, &options);
+            let actual = scan_reader(input.as_bytes(), &options).expect("読み込みに失敗しました。");
+            assert_eq!(expected, actual, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn test_scan_sources() {
+        let tokens = scan_sources(&["1 +", "\n2"]).expect("スキャンに失敗しました。");
+        assert_eq!(
+            vec![
+                (0, TokenType::Number, 1),
+                (0, TokenType::Plus, 1),
+                (1, TokenType::Number, 2),
+                (1, TokenType::Eof, 2),
+            ],
+            tokens
+                .into_iter()
+                .map(|token| (token.file, token.token.ty, token.token.line))
+                .collect::<Vec<_>>()
+        );
+
+        let errors = scan_sources(&["@", "1", "#"]).expect_err("スキャンはエラーになるべきです。");
+        assert_eq!(
+            vec![
+                FileScanError {
+                    file: 0,
+                    error: ScanError {
+                        line: 1,
+                        column: 1,
+                        message: "invalid token: @".to_string(),
+                    },
+                },
+                FileScanError {
+                    file: 2,
+                    error: ScanError {
+                        line: 1,
+                        column: 1,
+                        message: "invalid token: #".to_string(),
+                    },
+                },
+            ],
+            errors
+        );
+    }
+
+    #[test]
+    fn test_resync_on_newline() {
+        let input = "1 @ # $ %\n2";
+
+        let (tokens, errors) = scan_tokens_collecting(input, &ScanOptions::default());
+        assert_eq!(4, errors.len());
+        assert_eq!(3, tokens.len());
+
+        let options = ScanOptions {
+            resync_on_newline: true,
+            ..Default::default()
+        };
+        let (tokens, errors) = scan_tokens_collecting(input, &options);
+        assert_eq!(
+            vec![ScanError {
+                line: 1,
+                column: 3,
+                message: "invalid token: @".to_string(),
+            }],
+            errors
+        );
+        assert_eq!(
+            vec![
+                (TokenType::Number, 1),
+                (TokenType::Number, 2),
+                (TokenType::Eof, 2)
+            ],
+            tokens
+                .into_iter()
+                .map(|token| (token.ty, token.line))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_custom_keywords() {
+        let options = ScanOptions {
+            keywords: HashMap::from([
+                ("let".to_string(), TokenType::Var),
+                ("function".to_string(), TokenType::Fun),
+                ("fun".to_string(), TokenType::Identifier),
+            ]),
+            ..Default::default()
+        };
+
+        let tokens = scan_tokens_with("let x = 1; function fun var", &options)
+            .expect("スキャンに失敗しました。");
+        assert_eq!(
+            vec![
+                TokenType::Var,
+                TokenType::Identifier,
+                TokenType::Equal,
+                TokenType::Number,
+                TokenType::SemiColon,
+                TokenType::Fun,
+                TokenType::Identifier,
+                TokenType::Var,
+                TokenType::Eof,
+            ],
+            tokens.into_iter().map(|token| token.ty).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_skip_bom() {
+        let tokens = scan_tokens("\u{feff}var x = 1;").expect("スキャンに失敗しました。");
+        assert_eq!(
+            vec![
+                (TokenType::Var, 1),
+                (TokenType::Identifier, 5),
+                (TokenType::Equal, 7),
+                (TokenType::Number, 9),
+                (TokenType::SemiColon, 10),
+                (TokenType::Eof, 11),
+            ],
+            tokens
+                .into_iter()
+                .map(|token| (token.ty, token.column))
+                .collect::<Vec<_>>()
+        );
+
+        let error = scan_tokens("1 \u{feff}").expect_err("スキャンはエラーになるべきです。");
+        assert_eq!(
+            ScanError {
+                line: 1,
+                column: 3,
+                message: "invalid token: \u{feff}".to_string(),
+            },
+            error
+        );
+    }
+
+    #[test]
+    fn test_logical_operator_hint() {
+        let (tokens, errors) = scan_tokens_collecting("a && b || c & d", &ScanOptions::default());
+        assert_eq!(
+            vec![
+                ScanError {
+                    line: 1,
+                    column: 3,
+                    message: "use 'and' instead of '&&'".to_string(),
+                },
+                ScanError {
+                    line: 1,
+                    column: 8,
+                    message: "use 'or' instead of '||'".to_string(),
+                },
+                ScanError {
+                    line: 1,
+                    column: 13,
+                    message: "invalid token: &".to_string(),
+                },
+            ],
+            errors
+        );
+        assert_eq!(5, tokens.len());
+    }
 }
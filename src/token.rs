@@ -1,15 +1,21 @@
+use std::rc::Rc;
+
+use serde::{Serialize, Serializer};
+
 /// `Token` 構造体は、字句解析器が生成するトークンを表します。
 ///
 /// トークンは、ソースコードを構成する個々の要素（キーワード、識別子、リテラルなど）に相当します。
-/// この構造体は、トークンの型、字句、リテラル値、およびトークンが現れるソースコード上の行番号を保持します。
+/// この構造体は、トークンの型、字句、リテラル値、およびトークンが現れるソースコード上の行番号と列番号を保持します。
 ///
 /// # フィールド
 ///
 /// * `ty` - `TokenType` 列挙型のインスタンスであり、トークンの型を表します。
 /// * `lexeme` - `Vec<char>` 型で、トークンの字句を文字のベクターとして保持します。
-/// ＊ `literal` - `Option<Literal>` 型で、トークンに関連つけられたリテラル値を表すオプション値です。
+/// * `literal` - `Option<Literal>` 型で、トークンに関連つけられたリテラル値を表すオプション値です。
 ///   これは、トークンがリテラル値を有さない型の場合には None になります。
 /// * `line` - `usize` 型で、トークンが見つかったソースコードの行番号を保持します。
+/// * `column` - `usize` 型で、トークンの最初の文字が行の何文字目に当たるのかを保持します。
+/// * `offset` - `usize` 型で、トークンの最初の文字がソースコードの先頭から何バイト目にあるのかを保持します。
 ///
 /// # 例
 ///
@@ -17,31 +23,96 @@
 /// let token = Token {
 ///     ty: TokenType::Identifier,
 ///     lexeme: vec!['f', 'i', 'v', 'e'],
-///     literal: Some(Literal::Identifier("five".to_string())),
+///     literal: Some(Literal::Identifier("five".into())),
 ///     line: 1,
+///     column: 5,
+///     offset: 4,
 /// };
 /// ```
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Serialize)]
 pub struct Token {
     /// トークンの型を表します
     pub ty: TokenType,
     /// トークンの字句を保持する文字のベクター
+    ///
+    /// JSON へ変換する際は1文字ずつの配列ではなく文字列として出力する
+    #[serde(serialize_with = "serialize_lexeme")]
     pub lexeme: Vec<char>,
     /// トークンに関連するリテラル値、リテラルではない場合は `None`
     pub literal: Option<Literal>,
-    /// トークンが見つかったソースコードの行番号
+    /// トークンの最初の文字があるソースコードの行番号
+    ///
+    /// 複数行にまたがる文字列リテラルでは、開始のダブルクォートがある行を指す
     pub line: usize,
+    /// トークンの最初の文字が行の何文字目に当たるのか (1始まり)
+    ///
+    /// タブ文字の幅は `ScanOptions::tab_width` に従って数える
+    pub column: usize,
+    /// トークンの最初の文字がソースコードの先頭から何バイト目にあるのか (0始まり)
+    ///
+    /// `token_text` で元のソースコードからトークンの範囲を切り出すために利用する
+    pub offset: usize,
+}
+
+impl Token {
+    /// リテラル値を持たないトークンを、字句解析を経由せずに直接生成します。
+    ///
+    /// テストやトークン列を変換するツールで、構造体リテラルを書かずにトークンを組み立てるために利用します。
+    /// ソースコード上の位置を持たないため、列番号とバイト位置は `0` になります。
+    ///
+    /// # 例
+    ///
+    /// ```
+    /// let token = Token::synthetic(TokenType::Plus, "+", 1);
+    /// assert_eq!(token.lexeme, vec!['+']);
+    /// ```
+    pub fn synthetic(ty: TokenType, lexeme: &str, line: usize) -> Token {
+        Token {
+            ty,
+            lexeme: lexeme.chars().collect(),
+            literal: None,
+            line,
+            column: 0,
+            offset: 0,
+        }
+    }
+
+    /// ソースコード上の位置を `(line, column)` として返します。
+    pub fn position(&self) -> (usize, usize) {
+        (self.line, self.column)
+    }
+}
+
+/// ソースコード上の位置 `(line, column)` の順に比較します。
+///
+/// 位置が同じでも内容の異なるトークンは `PartialEq` で等しくないため、その場合は `None` を返します。
+/// `Literal` が `f64` を含むため `Ord` は実装していません。全順序が必要な場合は
+/// `sort_by_key(Token::position)` を利用してください。
+impl PartialOrd for Token {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        match self.position().cmp(&other.position()) {
+            std::cmp::Ordering::Equal if self != other => None,
+            ordering => Some(ordering),
+        }
+    }
+}
+
+fn serialize_lexeme<S: Serializer>(lexeme: &[char], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&lexeme.iter().collect::<String>())
 }
 
 /// `Literal` 列挙型 Lox 言語で使用する識別子の種類と実際のリテラル値を表します。
 ///
 /// この列挙型は、識別子、文字列リテラル、または数値リテラルを保持することができます。
-/// 各列挙子は、それぞれの値を `String` または `f64` として保持します。
+/// 各列挙子は、それぞれの値を `Rc<str>`、`String` または `f64` として保持します。
+///
+/// `true`、`false`、`nil` はキーワードとして `TokenType::True` などのトークン種別で表すため、
+/// この列挙型には含まれません。構文解析器はトークン種別から直接 `expr::Literal` を生成します。
 ///
 /// # 例
 ///
 /// ```
-/// let identifier = Literal::Identifier("myVar".to_string());
+/// let identifier = Literal::Identifier("myVar".into());
 /// let string = Literal::Str("Hello, world!".to_string());
 /// let number = Literal::Number(3.14);
 /// ```
@@ -52,10 +123,12 @@ pub struct Token {
 ///     ↓                   ↓
 ///     Identifier("name")  Str("keisuke")
 /// ```
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Serialize)]
 pub enum Literal {
-    /// 識別子を表す列挙子で、`String`型の値を保持します。
-    Identifier(String),
+    /// 識別子を表す列挙子で、`Rc<str>`型の値を保持します。
+    ///
+    /// 字句解析器は同じ名前の識別子で文字列を共有するため、複製しても文字列はコピーされません。
+    Identifier(Rc<str>),
     /// 文字列リテラルを表す列挙子で、`String`型の値を保持します。
     Str(String),
     /// 数値リテラルを表す列挙子で、`f64`型の値を保持します。
@@ -76,14 +149,17 @@ pub enum Literal {
 /// let keyword_token = TokenType::If;           // if
 /// let eof_token = TokenType::Eof;              //
 /// ```
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone, Serialize)]
 pub enum TokenType {
     // 記号1個のトークン
     LParan,
     RParan,
     LBrace,
     RBrace,
+    LBracket,
+    RBracket,
     Comma,
+    Colon,
     Dot,
     Minus,
     Plus,
@@ -100,6 +176,8 @@ pub enum TokenType {
     GreaterEqual,
     Less,
     LessEqual,
+    StarStar,
+    SlashEqual,
 
     // リテラル
     Identifier,
@@ -123,6 +201,10 @@ pub enum TokenType {
     This,
     Var,
     Print,
+    In,
+
+    // 改行を文の終端として扱うモードでのみ生成される
+    Newline,
 
     // End of file
     Eof,
@@ -137,7 +219,10 @@ impl std::fmt::Display for TokenType {
             RParan => ")",
             LBrace => "{{",
             RBrace => "}}",
+            LBracket => "[",
+            RBracket => "]",
             Comma => ",",
+            Colon => ":",
             Dot => ".",
             Minus => "-",
             Plus => "+",
@@ -152,6 +237,8 @@ impl std::fmt::Display for TokenType {
             GreaterEqual => ">=",
             Less => "<",
             LessEqual => "<=",
+            StarStar => "**",
+            SlashEqual => "/=",
             Identifier => "Identifier",
             String => "String",
             Number => "Number",
@@ -172,6 +259,8 @@ impl std::fmt::Display for TokenType {
             Var => "var",
             Eof => "eof",
             Print => "print",
+            In => "in",
+            Newline => "newline",
         };
 
         write!(f, "{matching_literal}")
@@ -209,6 +298,7 @@ pub fn match_keywords(literal: &str) -> Option<TokenType> {
         "for" => TokenType::For,
         "fun" => TokenType::Fun,
         "if" => TokenType::If,
+        "in" => TokenType::In,
         "nil" => TokenType::Nil,
         "or" => TokenType::Or,
         "print" => TokenType::Print,
@@ -226,3 +316,180 @@ pub fn match_keywords(literal: &str) -> Option<TokenType> {
         _ => Some(ty),
     }
 }
+
+/// トークン列をソースコードの文字列に復元します。
+///
+/// トークンの間は半角スペース1つで区切り、各トークンの行番号に合わせて改行を挿入します。
+/// 元の空白やコメントは失われるため列番号は変わりますが、出力を再び字句解析すると
+/// 行番号も含めて同じトークン列が得られます。
+///
+/// # 例
+///
+/// ```
+/// let tokens = scan_tokens("var x   =\n1;").unwrap();
+/// assert_eq!(reconstruct_source(&tokens), "var x =\n1 ;");
+/// ```
+pub fn reconstruct_source(tokens: &[Token]) -> String {
+    let mut source = String::new();
+    // 出力中の文字列の末尾が何行目に当たるのかを追跡する
+    let mut line = 1;
+
+    for token in tokens {
+        // 改行は行番号の変化から復元するため、改行トークンは出力しない
+        if matches!(token.ty, TokenType::Eof | TokenType::Newline) {
+            continue;
+        }
+
+        if token.line > line {
+            source.push_str(&"\n".repeat(token.line - line));
+        } else if !source.is_empty() {
+            source.push(' ');
+        }
+
+        // 複数行にまたがる文字列の後は、字句に含まれる改行の分だけ行が進む
+        let newlines = token.lexeme.iter().filter(|&&c| c == '\n').count();
+        source.extend(token.lexeme.iter());
+        line = token.line.max(line) + newlines;
+    }
+
+    source
+}
+
+/// 元のソースコードから、トークンに対応する範囲をそのまま切り出します。
+///
+/// 文字列リテラルのエスケープシーケンスなど、ソースコード上の表記をそのまま返します。
+/// `source` はトークンを字句解析した入力と同じである必要があります。
+///
+/// # 例
+///
+/// ```
+/// let source = "print \"a\\n\";";
+/// let tokens = scan_tokens(source).unwrap();
+/// assert_eq!(token_text(source, &tokens[1]), "\"a\\n\"");
+/// ```
+pub fn token_text<'a>(source: &'a str, token: &Token) -> &'a str {
+    let len: usize = token.lexeme.iter().map(|c| c.len_utf8()).sum();
+    &source[token.offset..token.offset + len]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        scanner::scan_tokens,
+        token::{reconstruct_source, token_text, Token, TokenType},
+    };
+
+    #[test]
+    fn test_synthetic_token() {
+        assert_eq!(
+            Token {
+                ty: TokenType::Plus,
+                lexeme: vec!['+'],
+                literal: None,
+                line: 1,
+                column: 0,
+                offset: 0,
+            },
+            Token::synthetic(TokenType::Plus, "+", 1)
+        );
+    }
+
+    #[test]
+    fn test_reconstruct_source() {
+        let tokens = scan_tokens("var x = 1 ;").expect("スキャンに失敗しました。");
+        let source = reconstruct_source(&tokens);
+        assert_eq!("var x = 1 ;", source);
+        assert_eq!(
+            tokens.into_iter().map(|token| token.ty).collect::<Vec<_>>(),
+            scan_tokens(&source)
+                .expect("スキャンに失敗しました。")
+                .into_iter()
+                .map(|token| token.ty)
+                .collect::<Vec<_>>()
+        );
+
+        let input = "\n// comment\n[1,   2]\n\n+ \"a\nb\" +\n  3.5";
+        let tokens = scan_tokens(input).expect("スキャンに失敗しました。");
+        let source = reconstruct_source(&tokens);
+        assert_eq!("\n\n[ 1 , 2 ]\n\n+ \"a\nb\" +\n3.5", source);
+        // 空白が変わるため列番号は一致しないが、行番号は一致する
+        let positions = |tokens: Vec<Token>| {
+            tokens
+                .into_iter()
+                .map(|token| (token.ty, token.lexeme, token.line))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            positions(tokens),
+            positions(scan_tokens(&source).expect("スキャンに失敗しました。"))
+        );
+    }
+
+    #[test]
+    fn test_token_order() {
+        let tokens = scan_tokens("var a =\n  [1, 2];").expect("スキャンに失敗しました。");
+        let positions = tokens.iter().map(Token::position).collect::<Vec<_>>();
+
+        // 決まった順序で並べ替えた後、元の順序に戻ることを確認する
+        let mut shuffled = tokens;
+        shuffled.reverse();
+        shuffled.swap(0, 3);
+        shuffled.swap(2, 7);
+        assert_ne!(
+            positions,
+            shuffled.iter().map(Token::position).collect::<Vec<_>>()
+        );
+
+        shuffled.sort_by(|a, b| a.partial_cmp(b).expect("positions should be distinct"));
+        assert_eq!(
+            positions,
+            shuffled.iter().map(Token::position).collect::<Vec<_>>()
+        );
+
+        let plus = Token::synthetic(TokenType::Plus, "+", 1);
+        let minus = Token::synthetic(TokenType::Minus, "-", 1);
+        assert_eq!(None, plus.partial_cmp(&minus));
+        assert!(plus < Token::synthetic(TokenType::Plus, "+", 2));
+    }
+
+    #[test]
+    fn test_token_text() {
+        let source = "// コメント\nvar name = \"a\\tb\\u{1F600}\";";
+        let tokens = scan_tokens(source).expect("スキャンに失敗しました。");
+
+        assert_eq!(
+            vec!["var", "name", "=", "\"a\\tb\\u{1F600}\"", ";", ""],
+            tokens
+                .iter()
+                .map(|token| token_text(source, token))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_sort_tokens_around_multiline_string() {
+        let tokens = scan_tokens("1 + \"a\nb\" + 2").expect("スキャンに失敗しました。");
+        assert_eq!((1, 5), tokens[2].position());
+        assert_eq!((2, 4), tokens[3].position());
+
+        let positions = tokens.iter().map(Token::position).collect::<Vec<_>>();
+        let mut sorted = tokens;
+        sorted.reverse();
+        sorted.sort_by(|a, b| a.partial_cmp(b).expect("positions should be distinct"));
+        assert_eq!(
+            positions,
+            sorted.iter().map(Token::position).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![
+                TokenType::Number,
+                TokenType::Plus,
+                TokenType::String,
+                TokenType::Plus,
+                TokenType::Number,
+                TokenType::Eof,
+            ],
+            sorted.into_iter().map(|token| token.ty).collect::<Vec<_>>()
+        );
+    }
+}
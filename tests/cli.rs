@@ -0,0 +1,174 @@
+use std::{fs, path::PathBuf, process::Command};
+
+/// 統合テスト用の一時ファイルにソースコードを書き出し、そのパスを返す
+fn write_source(name: &str, source: &str) -> PathBuf {
+    let path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    fs::write(&path, source).expect("Failed to write source file");
+    path
+}
+
+fn run(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_rust-template"))
+        .args(args)
+        .output()
+        .expect("Failed to run binary")
+}
+
+#[test]
+fn test_dump_tokens_json() {
+    let path = write_source("dump_tokens.lox", "var five = 5;\n");
+
+    let output = run(&["--dump-tokens-json", path.to_str().unwrap()]);
+    assert_eq!(Some(0), output.status.code());
+
+    let json: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("stdout should be valid JSON");
+    let tokens = json.as_array().expect("JSON should be an array of tokens");
+    assert_eq!(6, tokens.len());
+    assert_eq!("Var", tokens[0]["ty"]);
+    assert_eq!("five", tokens[1]["lexeme"]);
+    assert_eq!(5.0, tokens[3]["literal"]["Number"]);
+    assert_eq!("Eof", tokens[5]["ty"]);
+}
+
+#[test]
+fn test_dump_tokens_json_scan_error() {
+    let path = write_source("dump_tokens_error.lox", "var x = @;\n");
+
+    let output = run(&["--dump-tokens-json", path.to_str().unwrap()]);
+    assert_eq!(Some(65), output.status.code());
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("invalid token: @"));
+}
+
+#[test]
+fn test_run_file_reports_all_errors() {
+    let path = write_source("run_file_errors.lox", "(1 +\n2 @ 3\n");
+
+    let output = run(&[path.to_str().unwrap()]);
+    assert_eq!(Some(65), output.status.code());
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        "[line 2] Error: invalid token: @\n\
+         [line 2] Error: at '3', expect ')' after expression\n",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn test_diagnostics_json() {
+    let path = write_source("diagnostics.lox", "(1 +\n2 @ 3\n");
+
+    let output = run(&["--diagnostics-json", path.to_str().unwrap()]);
+    assert_eq!(Some(0), output.status.code());
+
+    let json: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("stdout should be valid JSON");
+    assert_eq!(
+        serde_json::json!([
+            {"line": 2, "column": 3, "severity": "error", "message": "invalid token: @"},
+            {"line": 2, "column": 5, "severity": "error", "message": "at '3', expect ')' after expression"},
+        ]),
+        json
+    );
+}
+
+#[test]
+fn test_diagnostics_json_without_errors() {
+    let path = write_source("diagnostics_ok.lox", "1 + 2\n");
+
+    let output = run(&["--diagnostics-json", path.to_str().unwrap()]);
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!("[]\n", String::from_utf8_lossy(&output.stdout));
+}
+
+#[test]
+fn test_check() {
+    let path = write_source("check_ok.lox", "[1, 2][0] ** 2\n");
+
+    let output = run(&["--check", path.to_str().unwrap()]);
+    assert_eq!(Some(0), output.status.code());
+    assert!(output.stdout.is_empty());
+    assert!(output.stderr.is_empty());
+
+    let path = write_source("check_errors.lox", "(1 +\n2 @ 3\n");
+
+    let output = run(&["--check", path.to_str().unwrap()]);
+    assert_eq!(Some(65), output.status.code());
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        "[line 2] Error: invalid token: @\n\
+         [line 2] Error: at '3', expect ')' after expression\n",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn test_trailing_tokens() {
+    let path = write_source("trailing_tokens.lox", "1 2\n");
+
+    let output = run(&["--check", path.to_str().unwrap()]);
+    assert_eq!(Some(65), output.status.code());
+    assert_eq!(
+        "[line 1] Error: at '2', Expect end of expression\n",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let output = run(&[path.to_str().unwrap()]);
+    assert_eq!(Some(65), output.status.code());
+    assert!(output.stdout.is_empty());
+
+    let output = run(&["--diagnostics-json", path.to_str().unwrap()]);
+    let json: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("stdout should be valid JSON");
+    assert_eq!(1, json.as_array().expect("JSON should be an array").len());
+}
+
+#[test]
+fn test_precision_warning() {
+    let path = write_source("precision_warning.lox", "100000000000000000000000001\n");
+    let expected = "[line 1] Warning: integer literal 100000000000000000000000001 \
+                    cannot be represented exactly and becomes 100000000000000000000000000\n";
+
+    let output = run(&["--no-color", "--check", path.to_str().unwrap()]);
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!(expected, String::from_utf8_lossy(&output.stderr));
+
+    let output = run(&["--no-color", path.to_str().unwrap()]);
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!(expected, String::from_utf8_lossy(&output.stderr));
+    assert!(!output.stdout.is_empty());
+
+    let output = run(&["--diagnostics-json", path.to_str().unwrap()]);
+    let json: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("stdout should be valid JSON");
+    assert_eq!("warning", json[0]["severity"]);
+    assert_eq!(1, json[0]["column"]);
+}
+
+#[test]
+fn test_no_color() {
+    let path = write_source("no_color.lox", "1 @\n");
+
+    let output = run(&["--no-color", "--check", path.to_str().unwrap()]);
+    assert_eq!(Some(65), output.status.code());
+    assert_eq!(
+        "[line 1] Error: invalid token: @\n",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn test_time_reports_all_errors() {
+    let path = write_source("time_errors.lox", "(1 +\n2 @ 3\n");
+
+    let output = run(&["--no-color", "--time", path.to_str().unwrap()]);
+    assert_eq!(Some(65), output.status.code());
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("scan:  "));
+    assert!(stderr.ends_with(
+        "[line 2] Error: invalid token: @\n\
+         [line 2] Error: at '3', expect ')' after expression\n"
+    ));
+}